@@ -0,0 +1,25 @@
+// cargo bench --bench particle_update
+// move_particles + wall_collisions per step, with iter_mut against par_iter_mut
+use fluid_density_propeller_simulator::ParticleUpdateBench;
+use std::time::{Duration, Instant};
+
+const REPEATS: u32 = 50;
+
+fn time_per_step(bench: &mut ParticleUpdateBench, parallel_threshold: usize) -> Duration {
+    let started = Instant::now();
+    for _ in 0..REPEATS {
+        bench.step(parallel_threshold);
+    }
+    started.elapsed() / REPEATS
+}
+
+fn main() {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    println!("{} cores", cores);
+    for count in [400, 2000, 10000] {
+        let mut bench = ParticleUpdateBench::new(count);
+        let serial = time_per_step(&mut bench, usize::MAX);
+        let parallel = time_per_step(&mut bench, 0);
+        println!("{} particles, per step: serial {:?}, parallel {:?}", count, serial, parallel);
+    }
+}
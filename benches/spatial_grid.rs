@@ -0,0 +1,46 @@
+// cargo bench --bench spatial_grid
+// Contact search of compare_particles per step, every pair against the spatial grid
+use bevy::prelude::{Entity, Vec3};
+use fluid_density_propeller_simulator::{SpatialGrid, COLLISION_RADIUS};
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const REPEATS: u32 = 20;
+// the default bounding box
+const HALF_EXTENT: f32 = 5.0;
+
+fn time_per_step(mut step: impl FnMut() -> usize) -> (Duration, usize) {
+    let started = Instant::now();
+    let mut contacts = 0;
+    for _ in 0..REPEATS {
+        contacts = black_box(step());
+    }
+    (started.elapsed() / REPEATS, contacts)
+}
+
+fn main() {
+    let contact = 2.0 * COLLISION_RADIUS;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+    for count in [400, 2000] {
+        let positions: Vec<(Entity, Vec3)> = (0..count)
+            .map(|i| (Entity::from_raw(i as u32), Vec3::new(rng.gen_range(-HALF_EXTENT..HALF_EXTENT), rng.gen_range(-HALF_EXTENT..HALF_EXTENT), rng.gen_range(-HALF_EXTENT..HALF_EXTENT))))
+            .collect();
+        let positions = black_box(&positions);
+
+        let (all_pairs, all_pairs_contacts) = time_per_step(|| {
+            positions.iter().enumerate().map(|(i, &(_, a))| positions[i + 1..].iter().filter(|&&(_, b)| a.distance(b) <= contact).count()).sum()
+        });
+        // rebuilt every step as rebuild_spatial_grid does, each pair once as in compare_particles
+        let mut grid = SpatialGrid::new((2.0 * HALF_EXTENT).powi(3), count, COLLISION_RADIUS);
+        let (spatial_grid, grid_contacts) = time_per_step(|| {
+            grid.clear();
+            for &(entity, pos) in positions {
+                grid.insert(entity, pos);
+            }
+            positions.iter().map(|&(entity, pos)| grid.query_neighbors(pos, contact).filter(|&other| other > entity && pos.distance(positions[other.index() as usize].1) <= contact).count()).sum()
+        });
+        assert_eq!(grid_contacts, all_pairs_contacts);
+        println!("{} particles, per step: all pairs {:?}, spatial grid {:?}, {} contacts", count, all_pairs, spatial_grid, grid_contacts);
+    }
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::{Resource, Vec3};
+use serde::{Deserialize, Serialize};
+
+// How move_particles advances a particle over one step. The acceleration is a function of
+// position and velocity; with no body forces every scheme reduces to straight-line motion.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SolverMode {
+    #[default]
+    ForwardEuler,
+    Leapfrog,
+    RungeKutta4,
+}
+
+impl SolverMode {
+    pub fn step(self, position: Vec3, velocity: Vec3, dt: f32, acceleration: impl Fn(Vec3, Vec3) -> Vec3) -> (Vec3, Vec3) {
+        match self {
+            SolverMode::ForwardEuler => forward_euler(position, velocity, dt, acceleration),
+            SolverMode::Leapfrog => leapfrog(position, velocity, dt, acceleration),
+            SolverMode::RungeKutta4 => runge_kutta4(position, velocity, dt, acceleration),
+        }
+    }
+}
+
+// Velocity first, then position with the new velocity (semi-implicit, as the simulation
+// has always stepped)
+pub fn forward_euler(position: Vec3, velocity: Vec3, dt: f32, acceleration: impl Fn(Vec3, Vec3) -> Vec3) -> (Vec3, Vec3) {
+    let velocity = velocity + acceleration(position, velocity) * dt;
+    (position + velocity * dt, velocity)
+}
+
+// Kick-drift-kick, time reversible so energy errors stay bounded instead of growing
+pub fn leapfrog(position: Vec3, velocity: Vec3, dt: f32, acceleration: impl Fn(Vec3, Vec3) -> Vec3) -> (Vec3, Vec3) {
+    let half = velocity + acceleration(position, velocity) * dt * 0.5;
+    let position = position + half * dt;
+    (position, half + acceleration(position, half) * dt * 0.5)
+}
+
+// Classic fourth order Runge-Kutta on (position, velocity)
+pub fn runge_kutta4(position: Vec3, velocity: Vec3, dt: f32, acceleration: impl Fn(Vec3, Vec3) -> Vec3) -> (Vec3, Vec3) {
+    let (x1, v1) = (position, velocity);
+    let a1 = acceleration(x1, v1);
+    let (x2, v2) = (position + v1 * dt * 0.5, velocity + a1 * dt * 0.5);
+    let a2 = acceleration(x2, v2);
+    let (x3, v3) = (position + v2 * dt * 0.5, velocity + a2 * dt * 0.5);
+    let a3 = acceleration(x3, v3);
+    let (x4, v4) = (position + v3 * dt, velocity + a3 * dt);
+    let a4 = acceleration(x4, v4);
+    (position + (v1 + 2.0 * v2 + 2.0 * v3 + v4) * dt / 6.0, velocity + (a1 + 2.0 * a2 + 2.0 * a3 + a4) * dt / 6.0)
+}
@@ -0,0 +1,6817 @@
+// bevy systems take a parameter per resource and query they touch
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use bevy::ecs::schedule::{ExecutorKind, ScheduleLabel};
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::render::mesh::{shape, Indices, Mesh};// Import shapes correctly
+use bevy::render::render_resource::PrimitiveTopology;
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use nalgebra::{Vector3, DMatrix, DVector};
+use csv::{Writer, WriterBuilder};
+use serde::{Deserialize, Serialize};
+use clap::Parser;
+use std::error::Error;
+use std::io::{Seek, SeekFrom, Write};
+use std::collections::HashMap;
+use std::time::Instant;
+
+mod integrators;
+mod lookup;
+mod octree;
+mod sensitivity;
+mod torque_balance;
+use integrators::SolverMode;
+use lookup::interpolate_pitch_for_thrust;
+use octree::Octree;
+pub use sensitivity::{ParameterSensitivityMatrix, Sensitivity, SensitivityMatrix};
+pub use torque_balance::{BisectionResult, DualRotorTorqueBalance, TorqueBalance};
+
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+struct Particle {
+    velocity: Vec3,
+    mass: f32,
+    // centre of the box the particle is confined to, the origin unless batch domains are used
+    #[serde(default)]
+    domain_center: Vec3,
+}
+
+// Walls of each particle domain: particles are kept within half_extent of their
+// domain_center on every axis, and the same box is drawn. Elongate one axis for a wind tunnel.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct BoundingBox {
+    half_extent: Vec3,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        BoundingBox { half_extent: Vec3::splat(5.0) }
+    }
+}
+
+impl BoundingBox {
+    fn size(&self) -> Vec3 {
+        2.0 * self.half_extent
+    }
+
+    fn volume(&self) -> f32 {
+        self.size().x * self.size().y * self.size().z
+    }
+}
+
+// Random point in the domain box around center
+fn random_domain_position(rng: &mut impl Rng, center: Vec3, bounds: &BoundingBox) -> Vec3 {
+    let h = bounds.half_extent;
+    center + Vec3::new(rng.gen_range(-h.x..h.x), rng.gen_range(-h.y..h.y), rng.gen_range(-h.z..h.z))
+}
+
+// Camera placed on a sphere around target, angles in degrees
+#[derive(Component)]
+struct OrbitCamera {
+    yaw: f32,
+    pitch: f32,
+    radius: f32,
+    target: Vec3,
+}
+
+impl OrbitCamera {
+    fn from_position(position: Vec3, target: Vec3) -> Self {
+        let offset = position - target;
+        let radius = offset.length();
+        OrbitCamera {
+            yaw: offset.x.atan2(offset.z).to_degrees(),
+            pitch: (offset.y / radius).asin().to_degrees(),
+            radius,
+            target,
+        }
+    }
+
+    fn transform(&self) -> Transform {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        let offset = self.radius * Vec3::new(pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos());
+        Transform::from_translation(self.target + offset).looking_at(self.target, Vec3::Y)
+    }
+}
+
+// Angular state of a rotor, shared by every blade pointing at it
+#[derive(Component, Clone, Serialize, Deserialize)]
+struct PropellerHub {
+    rotation_z: f32,
+    old_rotation_z: f32,
+    angular_v: f32,
+    mass: f32, // of each blade
+    moi: f32, // whole rotor, kept current by update_propeller_moi
+    total_vertical_impulse: f32,
+    // in-plane impulse, non-zero once cyclic pitch tilts the disk force
+    total_x_impulse: f32,
+    total_z_impulse: f32,
+    // angular impulse the particles return to the rotor about its axis
+    total_reaction_torque: f32,
+    // angular_v range over the trial, deg/s
+    peak_angular_v: f32,
+    min_angular_v: f32,
+    rotation_direction: RotationDirection,
+    hub_geometry: HubGeometry,
+    // blade tip Mach number and the Prandtl-Glauert factor on strike impulses, kept current
+    // by update_compressibility
+    tip_mach: f32,
+    compressibility_factor: f32,
+    // blade reaction impulse in world axes, differs from the disk-frame totals once tilted
+    total_world_impulse: Vec3,
+    // out-of-balance mass at imbalance_radius along the first blade, shaking the hub on its mount
+    imbalance_mass: f32,
+    imbalance_radius: f32,
+    // hub displacement from its mount by the imbalance, and its largest length this trial
+    vibration_offset: Vec3,
+    peak_vibration: f32,
+}
+
+// Rotor mass imbalance applied to every hub, and the mount's compliance in m/N: the
+// centrifugal force m r omega^2 of the imbalance moves the hub by force * compliance
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct PropellerImbalance {
+    imbalance_mass: f32,
+    imbalance_radius: f32,
+    compliance: f32,
+}
+
+impl Default for PropellerImbalance {
+    fn default() -> Self {
+        PropellerImbalance { imbalance_mass: 0.0, imbalance_radius: 0.0, compliance: 1.0e-4 }
+    }
+}
+
+#[derive(Resource, Clone, Copy)]
+struct MountCompliance(f32);
+
+// Disk tilt of a tilt-rotor hub about axis, 0 spins about +Y like a helicopter and 90 puts
+// the disk vertical like an aeroplane propeller
+#[derive(Component, Clone, Copy)]
+struct TiltAngle {
+    angle_deg: f32,
+    axis: Vec3,
+}
+
+impl TiltAngle {
+    // disk frame to world
+    fn rotation(&self) -> Quat {
+        Quat::from_axis_angle(self.axis.normalize(), self.angle_deg.to_radians())
+    }
+}
+
+// Swings every tilting disk towards vertical at tilt_rate_deg_per_second, stopping at 90
+fn animate_tilt(mut query: Query<&mut TiltAngle>, config: Res<SimConfig>, time: Res<Time>) {
+    for mut tilt in query.iter_mut() {
+        tilt.angle_deg = (tilt.angle_deg + config.tilt_rate_deg_per_second * time.delta_seconds()).min(90.0);
+    }
+}
+
+// Hub flange and spinner, a solid disk on the axis
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct HubGeometry {
+    radius: f32,
+    mass: f32,
+}
+
+impl Default for HubGeometry {
+    fn default() -> Self {
+        HubGeometry { radius: 0.3, mass: 2.0 }
+    }
+}
+
+impl HubGeometry {
+    fn moi(&self) -> f32 {
+        0.5 * self.mass * self.radius * self.radius
+    }
+}
+
+// Which way rotation_z runs. angular_v stays the speed, the direction only signs the
+// angle increment and the blade velocity.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum RotationDirection {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+impl RotationDirection {
+    fn sign(self) -> f32 {
+        match self {
+            RotationDirection::Clockwise => 1.0,
+            RotationDirection::CounterClockwise => -1.0,
+        }
+    }
+
+    fn reversed(self) -> Self {
+        match self {
+            RotationDirection::Clockwise => RotationDirection::CounterClockwise,
+            RotationDirection::CounterClockwise => RotationDirection::Clockwise,
+        }
+    }
+}
+
+// Blade mesh entity driven by its hub. azimuth is the blade's angle around the hub in
+// degrees and offset the blade root's position relative to the hub.
+#[derive(Component)]
+struct PropellerBlade {
+    hub: Entity,
+    pitch: f32,
+    azimuth: f32,
+    offset: Vec3,
+    length: f32,
+    // radial stations for the BladeElement physics mode, root to tip
+    elements: Vec<BladeElement>,
+}
+
+// One radial strip of a blade. pitch, cl and cd hold the values of the last evaluation.
+#[derive(Clone, Copy)]
+struct BladeElement {
+    radius: f32,
+    chord: f32,
+    pitch: f32,
+    cl: f32,
+    cd: f32,
+}
+
+// How blade loads are found. MolecularDynamics strikes individual particles, BladeElement
+// integrates blade element momentum theory over each blade as a check on it.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum PhysicsMode {
+    MolecularDynamics,
+    BladeElement,
+}
+
+// element centres of count equal strips along the span
+fn blade_elements(geometry: &PropellerGeometry, count: usize, pitch: f32) -> Vec<BladeElement> {
+    let dr = geometry.span / count as f32;
+    (0..count)
+        .map(|i| {
+            let radius = (i as f32 + 0.5) * dr;
+            BladeElement { radius, chord: geometry.chord_at(radius), pitch, cl: 0.0, cd: 0.0 }
+        })
+        .collect()
+}
+
+// Pitch varying once per revolution, as a helicopter swashplate does to tilt the thrust.
+// Absent, every blade holds its base pitch.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct CyclicPitch {
+    amplitude_deg: f32,
+    phase_deg: f32,
+}
+
+impl CyclicPitch {
+    // base pitch plus the cyclic term at a blade's current angle, degrees
+    fn pitch_at(&self, base_pitch: f32, blade_rotation_deg: f32) -> f32 {
+        base_pitch + self.amplitude_deg * (blade_rotation_deg - self.phase_deg).to_radians().sin()
+    }
+}
+
+// Tip vortex wake: circulation_gain scales the tip vortex strength with rotor speed,
+// helix_pitch is how far the wake drops per revolution. Absent, nothing is shed.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct WakeVortexConfig {
+    circulation_gain: f32,
+    helix_pitch: f32,
+}
+
+// Helical tip vortex shed by a hub's blades, on its own entity. radius is the tip
+// radius, strength the circulation refreshed from the hub's angular velocity every step.
+#[derive(Component)]
+struct WakeVortex {
+    hub: Entity,
+    strength: f32,
+    radius: f32,
+    helix_pitch: f32,
+}
+
+impl WakeVortex {
+    // Turns of the helix drawn below the disk
+    const DRAWN_TURNS: usize = 4;
+
+    // Point of the helix shed age_rad ago, disk frame. The tip sheds at tip_azimuth and the
+    // filament drops helix_pitch per revolution as the blade moves on.
+    fn helix_point(&self, tip_azimuth: f32, direction: f32, age_rad: f32) -> Vec3 {
+        let azimuth = tip_azimuth - direction * age_rad;
+        Vec3::new(self.radius * azimuth.sin(), -self.helix_pitch * age_rad / std::f32::consts::TAU, self.radius * azimuth.cos())
+    }
+
+    // Velocity induced at hub-relative disk frame position rel by the nearest turn of the
+    // helix, taken as a straight filament: v = strength / (2 pi r), circling the filament so the
+    // flow inside the wake is pushed down. r is floored at a tenth of the radius, the core.
+    fn induced_velocity(&self, rel: Vec3, tip_azimuth: f32, direction: f32) -> Vec3 {
+        let azimuth = azimuth_of(rel);
+        // how long ago, in radians of rotation, the tip passed this azimuth
+        let age = (direction * (tip_azimuth - azimuth)).rem_euclid(std::f32::consts::TAU);
+        let turns = ((-rel.y / self.helix_pitch) - age / std::f32::consts::TAU).round().max(0.0);
+        let filament = self.helix_point(tip_azimuth, direction, age + turns * std::f32::consts::TAU);
+        let tangent = -Vec3::new(azimuth.cos(), 0.0, -azimuth.sin());
+        let offset = rel - filament;
+        let distance = offset.length().max(0.1 * self.radius);
+        self.strength / (std::f32::consts::TAU * distance) * tangent.cross(offset).normalize_or_zero()
+    }
+}
+
+// Refreshes each vortex from its hub and adds the induced velocity, per second of exposure,
+// to particles within 3 radii of the hub
+fn apply_wake_vortex(mut vortex_query: Query<&mut WakeVortex>, hub_query: Query<(&PropellerHub, &Transform, Option<&TiltAngle>)>, blade_query: Query<&PropellerBlade>,
+mut particle_query: Query<(&Transform, &mut Particle), Without<PropellerHub>>, wake: Res<WakeVortexConfig>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for mut vortex in vortex_query.iter_mut() {
+        let Ok((hub, hub_transform, tilt)) = hub_query.get(vortex.hub) else {
+            continue;
+        };
+        vortex.strength = wake.circulation_gain * hub.angular_v.to_radians().abs() * vortex.radius * vortex.radius;
+        let Some(blade) = blade_query.iter().find(|b| b.hub == vortex.hub) else {
+            continue;
+        };
+        let tip_azimuth = (hub.rotation_z + blade.azimuth).to_radians();
+        let direction = hub.rotation_direction.sign();
+        let to_world = tilt.map_or(Quat::IDENTITY, |t| t.rotation());
+        let reach = 3.0 * vortex.radius;
+        for (transform, mut particle) in particle_query.iter_mut() {
+            let rel = to_world.inverse() * (transform.translation - hub_transform.translation);
+            if rel.length_squared() > reach * reach {
+                continue;
+            }
+            particle.velocity += to_world * vortex.induced_velocity(rel, tip_azimuth, direction) * dt;
+        }
+    }
+}
+
+// Bulk downwash below each disk: a downward body force of
+// strength * angular_v^2 * exp(-decay_rate |r|) * exp(-decay_rate |y|), r off the spin axis and
+// y below the disk, on particles within radius of the axis. Absent, only blade strikes push fluid.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct Downwash {
+    strength: f32,
+    radius: f32,
+    decay_rate: f32,
+}
+
+impl Downwash {
+    // Force at hub-relative disk frame position rel for a rotor spinning at angular_v rad/s
+    fn force(&self, rel: Vec3, angular_v: f32) -> f32 {
+        let r = Vec2::new(rel.x, rel.z).length();
+        if rel.y >= 0.0 || r > self.radius {
+            return 0.0;
+        }
+        self.strength * angular_v * angular_v * (-self.decay_rate * r).exp() * (-self.decay_rate * rel.y.abs()).exp()
+    }
+}
+
+fn apply_downwash(hub_query: Query<(&PropellerHub, &Transform, Option<&TiltAngle>)>, mut particle_query: Query<(&Transform, &mut Particle), Without<PropellerHub>>,
+downwash: Res<Downwash>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for (hub, hub_transform, tilt) in hub_query.iter() {
+        let angular_v = hub.angular_v.to_radians();
+        let to_world = tilt.map_or(Quat::IDENTITY, |t| t.rotation());
+        let down = to_world * Vec3::NEG_Y;
+        for (transform, mut particle) in particle_query.iter_mut() {
+            let rel = to_world.inverse() * (transform.translation - hub_transform.translation);
+            let force = downwash.force(rel, angular_v);
+            if force > 0.0 {
+                let mass = particle.mass;
+                particle.velocity += down * force / mass * dt;
+            }
+        }
+    }
+}
+
+// Annular duct around each rotor, centred on the hub and running length along the spin axis,
+// which is Y in the disk frame. Particles bounce off all four faces; what they push on the
+// duct is collected in DuctThrust.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct DuctGeometry {
+    inner_radius: f32,
+    outer_radius: f32,
+    length: f32,
+}
+
+impl DuctGeometry {
+    // Outward normal of the nearest face and how deep a particle of radius margin at
+    // rel, relative to the hub in the disk frame, is into the duct wall, None when it is clear
+    fn contact(&self, rel: Vec3, margin: f32) -> Option<(Vec3, f32)> {
+        let r = Vec2::new(rel.x, rel.z).length();
+        let (inner, outer, half) = (self.inner_radius - margin, self.outer_radius + margin, self.length / 2.0 + margin);
+        if r <= inner || r >= outer || rel.y.abs() >= half {
+            return None;
+        }
+        let radial = Vec3::new(rel.x, 0.0, rel.z) / r;
+        [(r - inner, -radial), (outer - r, radial), (half - rel.y, Vec3::Y), (half + rel.y, Vec3::NEG_Y)]
+            .into_iter()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(depth, normal)| (normal, depth))
+    }
+
+    // Pushes a particle out of the duct of the hub at hub_position, tilted by to_world from
+    // the disk frame, and reflects it off the face it entered. Returns the impulse the
+    // particle gave the duct when it was heading in.
+    fn collide(&self, hub_position: Vec3, to_world: Quat, transform: &mut Transform, particle: &mut Particle) -> Option<Vec3> {
+        let (normal, depth) = self.contact(to_world.inverse() * (transform.translation - hub_position), COLLISION_RADIUS)?;
+        let normal = to_world * normal;
+        transform.translation += normal * depth;
+        let approach = particle.velocity.dot(normal);
+        if approach >= 0.0 {
+            return None;
+        }
+        let delta_v = -2.0 * approach * normal;
+        particle.velocity += delta_v;
+        Some(-particle.mass * delta_v)
+    }
+
+    // Inner and outer walls and the two lips, each a strip between two concentric circles
+    fn mesh(&self) -> Mesh {
+        const SEGMENTS: u32 = 64;
+        let half = self.length / 2.0;
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut strip = |a: (f32, f32), b: (f32, f32), normal: &dyn Fn(Vec3) -> Vec3| {
+            let base = positions.len() as u32;
+            for i in 0..=SEGMENTS {
+                let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+                let radial = Vec3::new(angle.sin(), 0.0, angle.cos());
+                for (radius, y) in [a, b] {
+                    positions.push((radial * radius + Vec3::Y * y).to_array());
+                    normals.push(normal(radial).to_array());
+                }
+            }
+            for i in 0..SEGMENTS {
+                let k = base + 2 * i;
+                indices.extend([k, k + 1, k + 2, k + 1, k + 3, k + 2]);
+            }
+        };
+        strip((self.inner_radius, -half), (self.inner_radius, half), &|radial| -radial);
+        strip((self.outer_radius, -half), (self.outer_radius, half), &|radial| radial);
+        strip((self.inner_radius, half), (self.outer_radius, half), &|_| Vec3::Y);
+        strip((self.inner_radius, -half), (self.outer_radius, -half), &|_| Vec3::NEG_Y);
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+// Reaction impulse of the duct walls this trial, summed over the rotors, and how many
+// particles hit them. Kept apart from the rotors' total_vertical_impulse and the box walls.
+#[derive(Resource, Default)]
+struct DuctThrust {
+    impulse: Vec3,
+    wall_hits: u32,
+}
+
+impl DuctThrust {
+    fn reset(&mut self) {
+        *self = DuctThrust::default();
+    }
+}
+
+fn duct_collisions(hub_query: Query<(&Transform, Option<&TiltAngle>), With<PropellerHub>>, mut particle_query: Query<(&mut Transform, &mut Particle), Without<PropellerHub>>,
+duct: Res<DuctGeometry>, mut thrust: ResMut<DuctThrust>) {
+    let hubs: Vec<(Vec3, Quat)> = hub_query.iter().map(|(transform, tilt)| (transform.translation, tilt.map_or(Quat::IDENTITY, TiltAngle::rotation))).collect();
+    for (mut transform, mut particle) in particle_query.iter_mut() {
+        for &(hub_position, to_world) in &hubs {
+            if let Some(impulse) = duct.collide(hub_position, to_world, &mut transform, &mut particle) {
+                thrust.impulse += impulse;
+                thrust.wall_hits += 1;
+            }
+        }
+    }
+}
+
+// The rendered duct of one rotor
+#[derive(Component)]
+struct DuctMesh {
+    hub: Entity,
+}
+
+fn spawn_duct_mesh(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>,
+duct: Res<DuctGeometry>, hub_query: Query<Entity, With<PropellerHub>>) {
+    let mesh = meshes.add(duct.mesh());
+    let material = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.7, 0.7, 0.75, 0.6),
+        alpha_mode: AlphaMode::Blend,
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    });
+    for hub in hub_query.iter() {
+        commands.spawn((PbrBundle { mesh: mesh.clone(), material: material.clone(), ..default() }, DuctMesh { hub }));
+    }
+}
+
+// Keeps each duct on its hub as the mount shakes and the disk tilts
+fn follow_hub_duct(mut duct_query: Query<(&DuctMesh, &mut Transform)>, hub_query: Query<(&Transform, Option<&TiltAngle>), (With<PropellerHub>, Without<DuctMesh>)>) {
+    for (duct, mut transform) in duct_query.iter_mut() {
+        if let Ok((hub_transform, tilt)) = hub_query.get(duct.hub) {
+            transform.translation = hub_transform.translation;
+            transform.rotation = tilt.map_or(Quat::IDENTITY, TiltAngle::rotation);
+        }
+    }
+}
+
+// The helix below each hub, DRAWN_TURNS turns from the first blade's tip
+fn draw_wake_vortex(mut gizmos: Gizmos, vortex_query: Query<&WakeVortex>, hub_query: Query<(&PropellerHub, &Transform, Option<&TiltAngle>)>, blade_query: Query<&PropellerBlade>) {
+    for vortex in vortex_query.iter() {
+        let Ok((hub, hub_transform, tilt)) = hub_query.get(vortex.hub) else {
+            continue;
+        };
+        let Some(blade) = blade_query.iter().find(|b| b.hub == vortex.hub) else {
+            continue;
+        };
+        let tip_azimuth = (hub.rotation_z + blade.azimuth).to_radians();
+        let direction = hub.rotation_direction.sign();
+        let to_world = tilt.map_or(Quat::IDENTITY, |t| t.rotation());
+        let segments = WakeVortex::DRAWN_TURNS * 32;
+        let max_age = WakeVortex::DRAWN_TURNS as f32 * std::f32::consts::TAU;
+        gizmos.linestrip(
+            (0..=segments).map(|i| hub_transform.translation + to_world * vortex.helix_point(tip_azimuth, direction, max_age * i as f32 / segments as f32)),
+            Color::CYAN,
+        );
+    }
+}
+
+fn effective_pitch(cyclic: Option<&CyclicPitch>, base_pitch: f32, blade_rotation_deg: f32) -> f32 {
+    cyclic.map_or(base_pitch, |cyclic| cyclic.pitch_at(base_pitch, blade_rotation_deg))
+}
+
+// How the hubs of a PropellerArray are laid out, all rotors spin about +Y
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum ArrayArrangement {
+    Grid { spacing: f32 }, // square grid in the XZ plane
+    Ring { radius: f32 },
+    Tandem { separation: f32 }, // stacked along the rotor axis
+    Row { spacing: f32 }, // along +X from the origin, one per BatchMode domain
+}
+
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct PropellerArray {
+    count: u32,
+    arrangement: ArrayArrangement,
+    // the first rotor's direction, every other rotor turns the opposite way when counter_rotating
+    #[serde(default)]
+    rotation_direction: RotationDirection,
+    #[serde(default)]
+    counter_rotating: bool,
+    // ConstantRPM target of every other rotor as a fraction of the first's, None runs them all at target_rpm
+    #[serde(default)]
+    alternate_rpm_ratio: Option<f32>,
+}
+
+impl Default for PropellerArray {
+    fn default() -> Self {
+        PropellerArray { count: 1, arrangement: ArrayArrangement::Grid { spacing: 8.0 }, rotation_direction: RotationDirection::Clockwise, counter_rotating: false, alternate_rpm_ratio: None }
+    }
+}
+
+impl PropellerArray {
+    fn rotation_direction(&self, index: usize) -> RotationDirection {
+        if self.counter_rotating && index % 2 == 1 {
+            self.rotation_direction.reversed()
+        } else {
+            self.rotation_direction
+        }
+    }
+
+    // the index-th rotor's share of the ConstantRPM target
+    fn rpm_ratio(&self, index: usize) -> f32 {
+        match self.alternate_rpm_ratio {
+            Some(ratio) if index % 2 == 1 => ratio,
+            _ => 1.0,
+        }
+    }
+
+    // hub positions, centred on the origin
+    fn hub_positions(&self) -> Vec<Vec3> {
+        let n = self.count as usize;
+        match self.arrangement {
+            ArrayArrangement::Grid { spacing } => {
+                let columns = (n as f32).sqrt().ceil() as usize;
+                let rows = (n + columns - 1) / columns.max(1);
+                (0..n)
+                    .map(|i| {
+                        let x = (i % columns) as f32 - (columns - 1) as f32 / 2.0;
+                        let z = (i / columns) as f32 - (rows - 1) as f32 / 2.0;
+                        Vec3::new(x, 0.0, z) * spacing
+                    })
+                    .collect()
+            }
+            ArrayArrangement::Ring { radius } => {
+                if n == 1 {
+                    return vec![Vec3::ZERO];
+                }
+                (0..n)
+                    .map(|i| {
+                        let angle = std::f32::consts::TAU * i as f32 / n as f32;
+                        Vec3::new(angle.cos(), 0.0, angle.sin()) * radius
+                    })
+                    .collect()
+            }
+            ArrayArrangement::Tandem { separation } => {
+                (0..n).map(|i| Vec3::Y * (i as f32 - (n - 1) as f32 / 2.0) * separation).collect()
+            }
+            ArrayArrangement::Row { spacing } => (0..n).map(|i| Vec3::X * i as f32 * spacing).collect(),
+        }
+    }
+}
+
+// Pitch of the blades on hub, all blades of a rotor share the sweep pitch
+fn hub_pitch<'a>(hub: Entity, blades: impl IntoIterator<Item = &'a PropellerBlade>) -> Option<f32> {
+    blades.into_iter().find(|blade| blade.hub == hub).map(|blade| blade.pitch)
+}
+
+// Sent by blade_collisions for every particle a blade strikes
+#[derive(Event, Clone)]
+struct BladeParticleCollision {
+    particle_entity: Entity,
+    position: Vec3,
+    impulse: Vec3,
+    blade_rotation_deg: f32,
+    pitch_deg: f32,
+}
+
+#[derive(Event)]
+struct ParticleParticleCollision {
+    entity_a: Entity,
+    entity_b: Entity,
+    // fixed-step elapsed time of the step that resolved the contact
+    time: f32,
+}
+
+// Vertical impulse on the blade binned by radial station, hub to tip
+#[derive(Resource)]
+struct BladeLoadDistribution {
+    blade_length: f32,
+    stations: Vec<f32>,
+}
+
+impl BladeLoadDistribution {
+    fn new(blade_length: f32, station_count: usize) -> Self {
+        BladeLoadDistribution { blade_length, stations: vec![0.0; station_count] }
+    }
+
+    fn add(&mut self, radius: f32, impulse: f32) {
+        let n = self.stations.len();
+        let i = ((radius / self.blade_length) * n as f32) as usize;
+        self.stations[i.min(n - 1)] += impulse;
+    }
+
+    fn reset(&mut self) {
+        for station in self.stations.iter_mut() {
+            *station = 0.0;
+        }
+    }
+}
+
+// Blade strikes over the whole sweep, binned by radius over blade length, hub to tip
+#[derive(Resource, Clone)]
+struct BladeHeatmap {
+    bins: Vec<u32>,
+    num_bins: usize,
+}
+
+impl BladeHeatmap {
+    fn new(num_bins: usize) -> Self {
+        BladeHeatmap { bins: vec![0; num_bins], num_bins }
+    }
+
+    fn add(&mut self, normalized_radius: f32) {
+        let i = (normalized_radius.clamp(0.0, 1.0) * self.num_bins as f32) as usize;
+        self.bins[i.min(self.num_bins - 1)] += 1;
+    }
+
+    // blue for an empty bin through to red for the busiest
+    fn color(&self, bin: usize) -> Color {
+        let max = self.bins.iter().copied().max().unwrap_or(0).max(1);
+        let t = self.bins[bin] as f32 / max as f32;
+        Color::rgb(t, 0.0, 1.0 - t)
+    }
+}
+
+// Particles within one chord of a rotor's disk plane, counted per polar cell every frame
+// and averaged over the rotors. data holds number densities, ring-major from the hub out.
+#[derive(Resource)]
+struct PolarDensityField {
+    ring_bins: usize,
+    angle_bins: usize,
+    data: Vec<f32>,
+}
+
+impl PolarDensityField {
+    fn new(ring_bins: usize, angle_bins: usize) -> Self {
+        PolarDensityField { ring_bins, angle_bins, data: vec![0.0; ring_bins * angle_bins] }
+    }
+
+    // radius and azimuth of a cell's centre for a disk of the given radius
+    fn cell_center(&self, ring: usize, sector: usize, radius: f32) -> (f32, f32) {
+        let dr = radius / self.ring_bins as f32;
+        let dtheta = std::f32::consts::TAU / self.angle_bins as f32;
+        ((ring as f32 + 0.5) * dr, (sector as f32 + 0.5) * dtheta)
+    }
+}
+
+// Density in the quarter turn just ahead of a blade over the quarter turn just behind it,
+// averaged over the frames of a trial. Blades push fluid forward as they sweep, so above 1
+// the rotor is compressing the fluid it meets.
+#[derive(Resource, Default)]
+struct CompressionRatio {
+    ratio: f32,
+    sum: f32,
+    samples: u32,
+}
+
+impl CompressionRatio {
+    fn mean(&self) -> f32 {
+        if self.samples == 0 { 0.0 } else { self.sum / self.samples as f32 }
+    }
+
+    fn reset(&mut self) {
+        self.sum = 0.0;
+        self.samples = 0;
+    }
+}
+
+fn compute_polar_density(mut field: ResMut<PolarDensityField>, mut compression: ResMut<CompressionRatio>, particle_query: Query<&Transform, With<Particle>>,
+hub_query: Query<(Entity, &PropellerHub, &Transform, Option<&TiltAngle>)>, blade_query: Query<&PropellerBlade>, geometry: Res<PropellerGeometry>) {
+    field.data.iter_mut().for_each(|cell| *cell = 0.0);
+    let rotors = hub_query.iter().count();
+    if rotors == 0 {
+        return;
+    }
+    // a quarter turn either side of the blade
+    let window = std::f32::consts::FRAC_PI_2;
+    let (ring_bins, angle_bins) = (field.ring_bins, field.angle_bins);
+    let (mut ahead, mut behind) = (0u32, 0u32);
+    // every rotor's disk counts into the same bins, averaged over the rotors below
+    for (hub_entity, hub, hub_transform, tilt) in hub_query.iter() {
+        let to_disk = tilt.map_or(Quat::IDENTITY, |t| t.rotation()).inverse();
+        let blade_angles: Vec<f32> = blade_query.iter().filter(|b| b.hub == hub_entity).map(|b| (hub.rotation_z + b.azimuth).to_radians()).collect();
+        let direction = hub.rotation_direction.sign();
+        for transform in particle_query.iter() {
+            let rel = to_disk * (transform.translation - hub_transform.translation);
+            let radius = Vec2::new(rel.x, rel.z).length();
+            if rel.y.abs() > geometry.chord || radius >= geometry.span {
+                continue;
+            }
+            let theta = azimuth_of(rel);
+            let ring = (radius / geometry.span * ring_bins as f32) as usize;
+            let sector = ((theta / std::f32::consts::TAU * angle_bins as f32) as usize).min(angle_bins - 1);
+            field.data[ring * angle_bins + sector] += 1.0;
+            // offset from the nearest blade, positive in the direction it turns
+            let offset = blade_angles.iter()
+                .map(|&blade| direction * ((theta - blade + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI))
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()));
+            match offset {
+                Some(offset) if offset > 0.0 && offset < window => ahead += 1,
+                Some(offset) if offset <= 0.0 && offset > -window => behind += 1,
+                _ => {}
+            }
+        }
+    }
+    let dr = geometry.span / ring_bins as f32;
+    let dtheta = std::f32::consts::TAU / angle_bins as f32;
+    for ring in 0..ring_bins {
+        let (inner, outer) = (ring as f32 * dr, (ring + 1) as f32 * dr);
+        let volume = 0.5 * (outer * outer - inner * inner) * dtheta * 2.0 * geometry.chord * rotors as f32;
+        for cell in &mut field.data[ring * angle_bins..(ring + 1) * angle_bins] {
+            *cell /= volume;
+        }
+    }
+    // both windows cover the same volume, so the count ratio is the density ratio
+    if behind > 0 {
+        compression.ratio = ahead as f32 / behind as f32;
+        compression.sum += compression.ratio;
+        compression.samples += 1;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct BeamElement {
+    length: f32,
+    width: f32,
+    thickness: f32,
+    elastic_modulus: f32,
+}
+
+impl BeamElement {
+    fn flexural_rigidity(&self) -> f32 {
+        self.elastic_modulus * self.width * self.thickness.powi(3) / 12.0
+    }
+}
+
+// Cantilevered Euler-Bernoulli beam chain, element 0 is clamped at the hub
+#[derive(Resource)]
+struct MeshDeformationSimulator {
+    elements: Vec<BeamElement>,
+}
+
+impl MeshDeformationSimulator {
+    // count equal lengths along the blade, each as wide as the chord at its middle
+    fn tapered(geometry: &PropellerGeometry, count: usize, elastic_modulus: f32) -> Self {
+        let length = geometry.span / count as f32;
+        let elements = (0..count)
+            .map(|e| BeamElement { length, width: geometry.chord_at((e as f32 + 0.5) * length), thickness: geometry.thickness, elastic_modulus })
+            .collect();
+        MeshDeformationSimulator { elements }
+    }
+
+    // loads[i] is the force carried by element i, lumped onto its outer node.
+    // Returns (tip deflection, root bending moment).
+    fn solve(&self, loads: &[f32]) -> (f32, f32) {
+        let n = self.elements.len();
+        let dofs = 2 * n; // (deflection, slope) per free node
+        let mut k = DMatrix::<f32>::zeros(dofs, dofs);
+        let mut f = DVector::<f32>::zeros(dofs);
+
+        for (e, element) in self.elements.iter().enumerate() {
+            let l = element.length;
+            let c = element.flexural_rigidity() / (l * l * l);
+            let ke = [
+                [12.0, 6.0 * l, -12.0, 6.0 * l],
+                [6.0 * l, 4.0 * l * l, -6.0 * l, 2.0 * l * l],
+                [-12.0, -6.0 * l, 12.0, -6.0 * l],
+                [6.0 * l, 2.0 * l * l, -6.0 * l, 4.0 * l * l],
+            ];
+            // global dof of local dof a, the clamped root node has none
+            let global = |a: usize| (2 * e + a).checked_sub(2);
+            for (a, row) in ke.iter().enumerate() {
+                for (b, &value) in row.iter().enumerate() {
+                    if let (Some(i), Some(j)) = (global(a), global(b)) {
+                        k[(i, j)] += c * value;
+                    }
+                }
+            }
+        }
+
+        let mut root_moment = 0.0;
+        let mut radius = 0.0;
+        for (e, element) in self.elements.iter().enumerate() {
+            radius += element.length;
+            let load = loads.get(e).copied().unwrap_or(0.0);
+            f[2 * e] = load;
+            root_moment += load * radius;
+        }
+
+        // the stiffness matrix is block tridiagonal (2x2 blocks per node)
+        let tip_deflection = match k.lu().solve(&f) {
+            Some(w) => w[dofs - 2],
+            None => f32::NAN,
+        };
+        (tip_deflection, root_moment)
+    }
+}
+
+// (time into the trial, thrust) per physics step, exported raw at the end of each trial
+// alongside the highest tip Mach number of the array at that step
+#[derive(Resource, Default, Clone)]
+struct ThrustHistory {
+    samples: Vec<(f32, f32)>,
+    tip_mach: Vec<f32>,
+    // first rotor's angle in radians when collection began, signed with its direction
+    start_rotation: f32,
+}
+
+impl ThrustHistory {
+    fn push(&mut self, dt: f32, thrust: f32, tip_mach: f32) {
+        let t = self.samples.last().map_or(0.0, |&(t, _)| t) + dt;
+        self.samples.push((t, thrust));
+        self.tip_mach.push(tip_mach);
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.tip_mach.clear();
+    }
+
+    fn mean(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().map(|&(_, thrust)| thrust).sum::<f32>() / self.samples.len() as f32
+    }
+
+    fn std_dev(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let variance = self.samples.iter().map(|&(_, thrust)| (thrust - mean) * (thrust - mean)).sum::<f32>() / self.samples.len() as f32;
+        variance.sqrt()
+    }
+}
+
+// Mean direction of angles in radians, in -pi..=pi; an arithmetic mean of 170 and -170
+// degrees would point the opposite way
+fn circular_mean(angles: impl Iterator<Item = f32>) -> f32 {
+    let (sin, cos) = angles.fold((0.0, 0.0), |(sin, cos), angle: f32| (sin + angle.sin(), cos + angle.cos()));
+    f32::atan2(sin, cos)
+}
+
+// First harmonics of the trial's thrust at multiples of the rotor's revolution rate,
+// (amplitude, phase in radians), and the strongest line of the whole spectrum
+#[derive(Resource, Default)]
+struct FourierAnalysis {
+    fundamental_hz: f32,
+    harmonics: Vec<(f32, f32)>,
+    peak_hz: f32,
+    // the peak is more than a frequency bin away from every harmonic
+    peak_off_harmonic: bool,
+}
+
+impl FourierAnalysis {
+    const HARMONICS: usize = 3;
+
+    // plain DFT of the mean-removed signal; a trial is a few thousand samples at most
+    fn compute(samples: &[(f32, f32)], fundamental_hz: f32) -> Self {
+        let n = samples.len();
+        if n < 2 {
+            return FourierAnalysis { fundamental_hz, harmonics: vec![(0.0, 0.0); Self::HARMONICS], ..Default::default() };
+        }
+        let mean = samples.iter().map(|&(_, thrust)| thrust).sum::<f32>() / n as f32;
+        let component = |frequency: f32| {
+            let (mut re, mut im) = (0.0, 0.0);
+            for &(t, thrust) in samples {
+                let angle = std::f32::consts::TAU * frequency * t;
+                re += (thrust - mean) * angle.cos();
+                im -= (thrust - mean) * angle.sin();
+            }
+            (2.0 * (re * re + im * im).sqrt() / n as f32, im.atan2(re))
+        };
+        let harmonics = (1..=Self::HARMONICS).map(|h| component(h as f32 * fundamental_hz)).collect();
+
+        // the strongest line of the spectrum should sit on one of the harmonics
+        let (mut peak_hz, mut peak_off_harmonic) = (0.0, false);
+        let duration = samples[n - 1].0 - samples[0].0;
+        if fundamental_hz > 0.0 && duration > 0.0 {
+            let resolution = 1.0 / duration;
+            let peak = (1..n / 2).map(|k| (k as f32 * resolution, component(k as f32 * resolution).0)).max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((peak, _)) = peak {
+                let nearest = (peak / fundamental_hz).round().clamp(1.0, Self::HARMONICS as f32) * fundamental_hz;
+                peak_hz = peak;
+                peak_off_harmonic = (peak - nearest).abs() > resolution;
+                if peak_off_harmonic {
+                    warn!("Thrust spectrum peaks at {} Hz, away from the {} Hz revolution rate and its harmonics", peak, fundamental_hz);
+                }
+            }
+        }
+        FourierAnalysis { fundamental_hz, harmonics, peak_hz, peak_off_harmonic }
+    }
+}
+
+// Per-frame thrust time series, (frame dt, thrust) pairs for the current trial
+#[derive(Resource, Default)]
+struct PropellerThrustRipple {
+    frame_impulse: f32,
+    samples: Vec<(f32, f32)>,
+}
+
+impl PropellerThrustRipple {
+    fn mean_thrust(&self) -> f32 {
+        let duration: f32 = self.samples.iter().map(|&(dt, _)| dt).sum();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        self.samples.iter().map(|&(dt, thrust)| thrust * dt).sum::<f32>() / duration
+    }
+
+    // (peak - mean) / mean
+    fn ripple_amplitude(&self) -> f32 {
+        let mean = self.mean_thrust();
+        let peak = self.samples.iter().map(|&(_, thrust)| thrust).fold(f32::MIN, f32::max);
+        if self.samples.is_empty() || mean == 0.0 {
+            return 0.0;
+        }
+        (peak - mean) / mean.abs()
+    }
+
+    // counts upward crossings of the mean
+    fn ripple_frequency_hz(&self) -> f32 {
+        let mean = self.mean_thrust();
+        let duration: f32 = self.samples.iter().map(|&(dt, _)| dt).sum();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        let crossings = self.samples.windows(2).filter(|w| w[0].1 < mean && w[1].1 >= mean).count();
+        crossings as f32 / duration
+    }
+
+    fn reset(&mut self) {
+        self.frame_impulse = 0.0;
+        self.samples.clear();
+    }
+}
+
+// (time, angular_v) from rest during the first trial of each pitch
+#[derive(Resource)]
+struct PropellerStartupTransient {
+    recording: bool,
+    elapsed: f32,
+    window: usize,
+    samples: Vec<(f32, f32)>,
+}
+
+impl Default for PropellerStartupTransient {
+    fn default() -> Self {
+        PropellerStartupTransient { recording: true, elapsed: 0.0, window: 30, samples: Vec::new() }
+    }
+}
+
+impl PropellerStartupTransient {
+    fn start(&mut self) {
+        self.recording = true;
+        self.elapsed = 0.0;
+        self.samples.clear();
+    }
+
+    // rolling average over the last window changed by less than 1%
+    fn is_steady(&self) -> bool {
+        let n = self.samples.len();
+        if n < 2 * self.window {
+            return false;
+        }
+        let average = |range: &[(f32, f32)]| range.iter().map(|&(_, w)| w).sum::<f32>() / range.len() as f32;
+        let previous = average(&self.samples[n - 2 * self.window..n - self.window]);
+        let current = average(&self.samples[n - self.window..]);
+        previous != 0.0 && ((current - previous) / previous).abs() < 0.01
+    }
+
+    // first time past 90% of the final window's average, the operating speed
+    fn time_to_operating_speed(&self) -> Option<f32> {
+        let tail = &self.samples[self.samples.len().saturating_sub(self.window)..];
+        if tail.is_empty() {
+            return None;
+        }
+        let steady = tail.iter().map(|&(_, w)| w).sum::<f32>() / tail.len() as f32;
+        self.samples.iter().find(|&&(_, w)| w > 0.9 * steady).map(|&(t, _)| t)
+    }
+
+    fn finish(&mut self, pitch: f32, logger: &DataLogger) {
+        self.recording = false;
+        info!("Time to steady state at pitch {}: {}", pitch, self.elapsed);
+        if let Some(time) = self.time_to_operating_speed() {
+            info!("Time to operating speed at pitch {}: {}", pitch, time);
+        }
+        let samples = self.samples.clone();
+        logger.write_file("startup transient CSV", move |dir| write_startup_transient(&dir.join(format!("startup_transient_{}.csv", pitch)), &samples));
+    }
+}
+
+// First moments of the vertical impulse about the hub, plus the yaw reaction
+#[derive(Resource, Default)]
+struct ThrustMomentCoupling {
+    mx: f32,
+    my: f32,
+    mz: f32,
+}
+
+// What sets the impulse of a blade strike
+#[derive(SystemParam)]
+struct StrikeModel<'w> {
+    profile: Res<'w, NacaProfile>,
+    restitution: Res<'w, Restitution>,
+    cyclic: Option<Res<'w, CyclicPitch>>,
+}
+
+// Where blade_collisions books the loads of each strike
+#[derive(SystemParam)]
+struct StrikeLoads<'w> {
+    blade_load: ResMut<'w, BladeLoadDistribution>,
+    ripple: ResMut<'w, PropellerThrustRipple>,
+    coupling: ResMut<'w, ThrustMomentCoupling>,
+    heatmap: ResMut<'w, BladeHeatmap>,
+}
+
+// Fluid medium, particles are spheres of this radius filled with fluid of this density.
+// Temperature and molecular mass set the Maxwell-Boltzmann spread of initial velocities.
+#[derive(Resource, Clone, Copy)]
+struct FluidDensity {
+    density_kg_per_m3: f32,
+    particle_radius: f32,
+    temperature_k: f32,
+    molecular_mass_kg: f32,
+    // J/(kg K), turns dissipated energy into a temperature rise of the whole fluid
+    specific_heat: f32,
+}
+
+// J/K
+const BOLTZMANN: f32 = 1.380649e-23;
+
+// Dynamic viscosity of the fluid, Pa s
+#[derive(Resource, Clone, Copy)]
+struct Viscosity(f32);
+
+impl Viscosity {
+    const AIR_SEA_LEVEL: Viscosity = Viscosity(1.81e-5);
+}
+
+// Bulk fluid temperature, K. Starts at FluidDensity::temperature_k and rises with the heat
+// collisions and viscous drag dissipate.
+#[derive(Resource, Clone, Copy)]
+struct FluidTemperature(f32);
+
+// Sutherland's law mu = mu_ref * (T / t_ref)^1.5 * (t_ref + s) / (T + s), taking Viscosity from
+// FluidTemperature every frame. temperature_gradient in K per unit height varies the
+// temperature each particle's drag sees with Y, e.g. -0.0065 for the atmospheric lapse rate.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct TemperatureDependentViscosity {
+    mu_ref: f32,
+    t_ref: f32,
+    s: f32,
+    temperature_gradient: Option<f32>,
+}
+
+impl Default for TemperatureDependentViscosity {
+    // air
+    fn default() -> Self {
+        TemperatureDependentViscosity { mu_ref: 1.716e-5, t_ref: 273.15, s: 110.4, temperature_gradient: None }
+    }
+}
+
+impl TemperatureDependentViscosity {
+    fn viscosity_at(&self, temperature: f32) -> f32 {
+        self.mu_ref * (temperature / self.t_ref).powf(1.5) * (self.t_ref + self.s) / (temperature + self.s)
+    }
+
+    // temperature at height y, the bulk temperature applying at y = 0
+    fn temperature_at(&self, bulk: f32, y: f32) -> f32 {
+        bulk + self.temperature_gradient.unwrap_or(0.0) * y
+    }
+}
+
+fn update_viscosity(mut viscosity: ResMut<Viscosity>, temperature: Res<FluidTemperature>, sutherland: Res<TemperatureDependentViscosity>) {
+    viscosity.0 = sutherland.viscosity_at(temperature.0);
+}
+
+// Stokes drag -6 pi eta r v on every particle when true, r being FluidDensity::particle_radius
+#[derive(Resource, Clone, Copy)]
+struct ViscousDragEnabled(bool);
+
+// Power the Stokes drag took out of the particles in the last physics step, and the energy
+// summed over the running trial
+#[derive(Resource, Default)]
+struct ViscousDissipation {
+    power: f32,
+    energy: f32,
+}
+
+// Blade tip Reynolds number, tip speed * chord / kinematic viscosity, averaged over the rotors
+#[derive(Resource, Default)]
+struct ReynoldsNumber(f32);
+
+fn compute_reynolds_number(mut reynolds: ResMut<ReynoldsNumber>, hub_query: Query<&PropellerHub>, geometry: Res<PropellerGeometry>, fluid: Res<FluidDensity>,
+viscosity: Res<Viscosity>) {
+    let rotors = hub_query.iter().count().max(1) as f32;
+    let tip_speed = hub_query.iter().map(|hub| hub.angular_v.to_radians().abs() * geometry.span).sum::<f32>() / rotors;
+    let kinematic_viscosity = viscosity.0 / fluid.density_kg_per_m3;
+    reynolds.0 = tip_speed * geometry.chord / kinematic_viscosity;
+}
+
+impl FluidDensity {
+    const AIR_SEA_LEVEL: FluidDensity = FluidDensity { density_kg_per_m3: 1.225, particle_radius: 0.1, temperature_k: 288.15, molecular_mass_kg: 4.81e-26,
+        specific_heat: 1005.0 };
+
+    // per velocity component, sqrt(kT/m)
+    fn thermal_speed(&self) -> f32 {
+        (BOLTZMANN * self.temperature_k / self.molecular_mass_kg).sqrt()
+    }
+
+    fn particle_mass(&self) -> f32 {
+        (4.0 / 3.0) * std::f32::consts::PI * self.particle_radius.powi(3) * self.density_kg_per_m3
+    }
+}
+
+impl Default for FluidDensity {
+    fn default() -> Self {
+        FluidDensity::AIR_SEA_LEVEL
+    }
+}
+
+// Flight altitude the fluid density is taken at, adjustable from the overlay
+#[cfg(feature = "ui")]
+#[derive(Resource, Clone, Copy)]
+struct AltitudeModel {
+    altitude_m: f32,
+}
+
+// International Standard Atmosphere troposphere, kg/m^3
+fn air_density_at_altitude(altitude_m: f32) -> f32 {
+    if altitude_m > 11000.0 {
+        warn!("Altitude {} m is above the troposphere, the ISA density formula no longer holds", altitude_m);
+    }
+    1.225 * (1.0 - 2.2558e-5 * altitude_m).max(0.0).powf(4.2561)
+}
+
+// How spawned and respawned particles get their velocity
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum VelocityDist {
+    // each component independently
+    Uniform { min: f32, max: f32 },
+    Gaussian { mean: Vec3, std_dev: f32 },
+    // gas in equilibrium, each component drawn from Normal(0, sqrt(kT/m)); temperature in K
+    // and molecular mass in kg are carried into FluidDensity
+    MaxwellBoltzmann { temperature: f32, mass: f32 },
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct ParticleInitConfig {
+    velocity_distribution: VelocityDist,
+}
+
+impl Default for ParticleInitConfig {
+    fn default() -> Self {
+        ParticleInitConfig { velocity_distribution: VelocityDist::Uniform { min: -1.0, max: 1.0 } }
+    }
+}
+
+impl VelocityDist {
+    fn sample(&self, fluid: &FluidDensity, rng: &mut impl Rng) -> Vec3 {
+        match *self {
+            VelocityDist::Uniform { min, max } => Vec3::new(rng.gen_range(min..max), rng.gen_range(min..max), rng.gen_range(min..max)),
+            VelocityDist::Gaussian { mean, std_dev } => {
+                let normal = Normal::new(0.0, std_dev).expect("std_dev is validated");
+                mean + Vec3::new(normal.sample(rng), normal.sample(rng), normal.sample(rng))
+            }
+            VelocityDist::MaxwellBoltzmann { .. } => {
+                let normal = Normal::new(0.0, fluid.thermal_speed()).expect("temperature and mass are validated");
+                Vec3::new(normal.sample(rng), normal.sample(rng), normal.sample(rng))
+            }
+        }
+    }
+}
+
+// Uniform hash grid of particle entities, rebuilt every frame. Cells start at the mean
+// spacing of the particles, never under one contact distance.
+#[derive(Resource)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<Entity>>,
+}
+
+impl SpatialGrid {
+    pub fn new(volume: f32, particle_count: usize, collision_radius: f32) -> Self {
+        let spacing = (volume / particle_count.max(1) as f32).cbrt();
+        SpatialGrid { cell_size: spacing.max(2.0 * collision_radius), cells: HashMap::new() }
+    }
+
+    fn cell_of(&self, pos: Vec3) -> IVec3 {
+        (pos / self.cell_size).floor().as_ivec3()
+    }
+
+    // keeps the cell allocations around for the next frame
+    pub fn clear(&mut self) {
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3) {
+        let cell = self.cell_of(pos);
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    // every entity in the cells overlapping the cube around pos, callers still check distance
+    pub fn query_neighbors(&self, pos: Vec3, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let center = self.cell_of(pos);
+        (-reach..=reach)
+            .flat_map(move |x| (-reach..=reach).flat_map(move |y| (-reach..=reach).map(move |z| center + IVec3::new(x, y, z))))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+// Mean particle velocity per voxel of a box below the rotor, rebuilt every physics step.
+// The axial component is the slip-stream the blades drive downwards.
+#[derive(Resource)]
+struct SlipStreamField {
+    cells: Vec<Vec3>,
+    resolution: UVec3,
+    // lowest corner and extent of the voxel box
+    min: Vec3,
+    size: Vec3,
+}
+
+impl SlipStreamField {
+    fn new(min: Vec3, size: Vec3, resolution: UVec3) -> Self {
+        SlipStreamField { cells: vec![Vec3::ZERO; (resolution.x * resolution.y * resolution.z) as usize], resolution, min, size }
+    }
+
+    fn cell_size(&self) -> Vec3 {
+        self.size / self.resolution.as_vec3()
+    }
+
+    fn index_of(&self, pos: Vec3) -> Option<usize> {
+        let cell = ((pos - self.min) / self.cell_size()).floor();
+        if cell.cmplt(Vec3::ZERO).any() || cell.cmpge(self.resolution.as_vec3()).any() {
+            return None;
+        }
+        let cell = cell.as_uvec3();
+        Some(((cell.z * self.resolution.y + cell.y) * self.resolution.x + cell.x) as usize)
+    }
+
+    fn cell_center(&self, index: usize) -> Vec3 {
+        let i = index as u32;
+        let cell = UVec3::new(i % self.resolution.x, i / self.resolution.x % self.resolution.y, i / (self.resolution.x * self.resolution.y));
+        self.min + (cell.as_vec3() + Vec3::splat(0.5)) * self.cell_size()
+    }
+
+    // mean vertical velocity over the voxels particles passed through, negative is downwash
+    fn mean_axial_velocity(&self) -> f32 {
+        let occupied: Vec<f32> = self.cells.iter().filter(|v| **v != Vec3::ZERO).map(|v| v.y).collect();
+        if occupied.is_empty() {
+            return 0.0;
+        }
+        occupied.iter().sum::<f32>() / occupied.len() as f32
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct PressureCell {
+    mean_speed_sq: f32,
+    count: u32,
+}
+
+// Relative static pressure from Bernoulli, P + 0.5 rho v^2 = constant, using the running
+// mean of |v|^2 per cell over the current trial
+#[derive(Resource)]
+struct PressureField {
+    cells: HashMap<IVec3, PressureCell>,
+    cell_size: f32,
+}
+
+impl PressureField {
+    fn new(cell_size: f32) -> Self {
+        PressureField { cells: HashMap::new(), cell_size }
+    }
+
+    fn cell_of(&self, pos: Vec3) -> IVec3 {
+        (pos / self.cell_size).floor().as_ivec3()
+    }
+
+    fn add_sample(&mut self, pos: Vec3, speed_sq: f32) {
+        let cell = self.cells.entry(self.cell_of(pos)).or_default();
+        cell.count += 1;
+        cell.mean_speed_sq += (speed_sq - cell.mean_speed_sq) / cell.count as f32;
+    }
+
+    // pressure relative to stagnation, always <= 0
+    fn pressure(cell: &PressureCell, density: f32) -> f32 {
+        -0.5 * density * cell.mean_speed_sq
+    }
+
+    fn layer_pressure(&self, layer: i32, center: Vec3, radius: f32, density: f32) -> Option<f32> {
+        let samples: Vec<f32> = self.cells.iter()
+            .filter(|(pos, _)| pos.y == layer)
+            .filter(|(pos, _)| {
+                let middle = (pos.as_vec3() + Vec3::splat(0.5)) * self.cell_size;
+                Vec2::new(middle.x - center.x, middle.z - center.z).length() <= radius
+            })
+            .map(|(_, cell)| Self::pressure(cell, density))
+            .collect();
+        if samples.is_empty() {
+            return None;
+        }
+        Some(samples.iter().sum::<f32>() / samples.len() as f32)
+    }
+
+    // mean pressure in the cell layer just below the disk minus the layer just above it
+    fn disk_pressure_difference(&self, center: Vec3, radius: f32, density: f32) -> Option<f32> {
+        let disk_layer = self.cell_of(center).y;
+        let below = self.layer_pressure(disk_layer - 1, center, radius, density)?;
+        let above = self.layer_pressure(disk_layer + 1, center, radius, density)?;
+        Some(below - above)
+    }
+}
+
+// How the rotor is driven. ConstantRPM holds the speed with a PID torque so different
+// pitches can be compared at the same RPM instead of the same power.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+enum RotorGovernor {
+    ConstantPower(f32),
+    ConstantRPM { target_rpm: f32, kp: f32, ki: f32, kd: f32 },
+}
+
+// Shaft work delivered to all rotors this trial, reset by the controller
+#[derive(Resource, Default)]
+struct GovernorState {
+    work: f32,
+}
+
+// PID memory of one hub's ConstantRPM governor, so every rotor holds its own speed. There is
+// no previous error before the first step, which keeps the derivative from kicking.
+#[derive(Component)]
+struct HubGovernor {
+    integral: f32,
+    previous_error: Option<f32>,
+    // J the governor has put into this rotor over the trial
+    work: f32,
+    // of target_rpm this rotor holds, PropellerArray::rpm_ratio
+    rpm_ratio: f32,
+}
+
+impl Default for HubGovernor {
+    fn default() -> Self {
+        HubGovernor { integral: 0.0, previous_error: None, work: 0.0, rpm_ratio: 1.0 }
+    }
+}
+
+impl HubGovernor {
+    fn reset(&mut self) {
+        self.integral = 0.0;
+        self.previous_error = None;
+        self.work = 0.0;
+    }
+}
+
+// What happens to a particle crossing a wall of the domain, chosen per axis
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum BoundaryCondition {
+    Reflect,
+    Wrap, // toroidal, re-enters through the opposite face
+    Absorb { respawn: bool },
+    OpenOutflow, // despawned like Absorb { respawn: false } and counted in OutflowFlux
+    // stream along inflow_face: particle_rate particles per second enter the upstream face
+    // at inflow_velocity, those reaching the downstream face go back to the pool. Upstream
+    // is the face inflow_velocity points away from.
+    WindTunnel { inflow_velocity: Vec3, inflow_face: Axis, particle_rate: f32 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+// +1 when the tunnel flows towards the positive face of its axis, -1 otherwise
+fn downstream_sign(inflow_velocity: Vec3, inflow_face: Axis) -> f32 {
+    if inflow_velocity[inflow_face.index()] < 0.0 { -1.0 } else { 1.0 }
+}
+
+// Momentum carried in and out by WindTunnel boundaries over the current trial; the deficit
+// is an independent estimate of the force on the rotor
+#[derive(Resource, Default)]
+struct WindTunnelFlux {
+    inflow_momentum: Vec3,
+    outflow_momentum: Vec3,
+    // fractional particle carried to the next step
+    accumulated: f32,
+}
+
+// x, y, z
+#[derive(Resource, Clone, Copy)]
+struct BoundaryConditions([BoundaryCondition; 3]);
+
+// Particles live in the domain, kept by ParticlePool as it hands particles out and takes them back
+#[derive(Resource, Default)]
+struct ParticleCount(usize);
+
+// Injects particles at position, e.g. a uniform upstream flow into the rotor. accumulated
+// carries the fractional particle over to the next step.
+#[derive(Component, Clone, Serialize, Deserialize)]
+struct ParticleEmitter {
+    position: Vec3,
+    rate_per_second: f32,
+    initial_velocity: Vec3,
+    velocity_spread: f32,
+    #[serde(default)]
+    accumulated: f32,
+}
+
+// Every particle entity, spawned once at startup so nothing allocates entities while the
+// simulation runs. A released particle is hidden and loses its Particle component, which
+// drops it from all physics, until acquire hands it out again.
+#[derive(Resource, Default)]
+struct ParticlePool {
+    available: std::collections::VecDeque<Entity>,
+    capacity: usize,
+}
+
+impl ParticlePool {
+    fn acquire(&mut self, commands: &mut Commands, count: &mut ParticleCount, transform: Transform, particle: Particle) -> Option<Entity> {
+        let entity = self.available.pop_front()?;
+        count.0 += 1;
+        commands.entity(entity).insert((particle, transform, Visibility::Visible, LastCollisionTime::default(), CollisionCount::default()));
+        Some(entity)
+    }
+
+    fn release(&mut self, commands: &mut Commands, count: &mut ParticleCount, entity: Entity) {
+        commands.entity(entity).remove::<Particle>().insert(Visibility::Hidden);
+        self.available.push_back(entity);
+        count.0 = count.0.saturating_sub(1);
+    }
+}
+
+// Fraction of the normal approach speed kept by a particle-particle collision and of the
+// impulse a blade strike imparts, 1.0 perfectly elastic, 0.0 perfectly inelastic
+#[derive(Resource, Clone, Copy)]
+struct Restitution {
+    particle_particle: f32,
+    blade_particle: f32,
+}
+
+// Kinetic energy inelastic particle contacts have turned into heat this trial, J
+#[derive(Resource, Default)]
+struct HeatGenerated(f32);
+
+// Specific heat of the fluid, J/(kg K), turns HeatGenerated into a temperature rise
+#[derive(Resource, Clone, Copy)]
+struct HeatCapacity(f32);
+
+// Particle count above which per-particle systems use par_iter_mut, below it the
+// thread pool overhead outweighs the work
+#[derive(Resource)]
+struct ParallelThreshold(usize);
+
+impl Default for ParallelThreshold {
+    fn default() -> Self {
+        ParallelThreshold(256)
+    }
+}
+
+// Gas-likeness of the particle fluid, refreshed every frame. A mean free path far below
+// the box size means the results come from particle jams more than from the rotor.
+#[derive(Resource, Default)]
+struct ParticleStatistics {
+    number_density: f32,
+    rms_speed: f32,
+    mean_free_path: f32,
+}
+
+// Particle-particle collisions as measured, against the kinetic theory ParticleStatistics
+// predicts. The mean time between collisions comes from the gap between sequential hits on
+// the same particle, and times the mean speed gives the measured mean free path.
+#[derive(Resource, Default)]
+struct ParticleParticleCollisionStats {
+    total_collisions_per_frame: u32,
+    mean_time_between_collisions: f32,
+    effective_mean_free_path: f32,
+    interval_sum: f32,
+    intervals: u32,
+}
+
+impl ParticleParticleCollisionStats {
+    // lambda_measured / lambda_theory, near 1 for a gas that behaves like one
+    fn mean_free_path_ratio(&self, theory: f32) -> Option<f32> {
+        (self.intervals > 0 && theory > 0.0 && theory.is_finite()).then(|| self.effective_mean_free_path / theory)
+    }
+
+    fn reset(&mut self) {
+        *self = ParticleParticleCollisionStats::default();
+    }
+}
+
+fn track_particle_collisions(mut collisions: EventReader<ParticleParticleCollision>, mut stats: ResMut<ParticleParticleCollisionStats>,
+mut query: Query<(&Particle, &mut LastCollisionTime)>) {
+    stats.total_collisions_per_frame = 0;
+    for event in collisions.read() {
+        stats.total_collisions_per_frame += 1;
+        for entity in [event.entity_a, event.entity_b] {
+            let Ok((_, mut last)) = query.get_mut(entity) else {
+                continue;
+            };
+            // hits within the same step are one contact with several neighbours
+            if let Some(previous) = last.particle.filter(|&t| t < event.time) {
+                stats.interval_sum += event.time - previous;
+                stats.intervals += 1;
+            }
+            last.particle = Some(event.time);
+        }
+    }
+    if stats.intervals > 0 {
+        let count = query.iter().count().max(1);
+        let mean_speed = query.iter().map(|(p, _)| p.velocity.length()).sum::<f32>() / count as f32;
+        stats.mean_time_between_collisions = stats.interval_sum / stats.intervals as f32;
+        stats.effective_mean_free_path = mean_speed * stats.mean_time_between_collisions;
+    }
+}
+
+fn compute_particle_statistics(mut stats: ResMut<ParticleStatistics>, query: Query<&Particle>, config: Res<SimConfig>) {
+    let count = query.iter().count();
+    let volume = config.bounding_box.volume() * config.domain_centers().len() as f32;
+    stats.number_density = count as f32 / volume;
+    stats.rms_speed = if count > 0 { (query.iter().map(|p| p.velocity.length_squared()).sum::<f32>() / count as f32).sqrt() } else { 0.0 };
+    // hard spheres of diameter 2 * COLLISION_RADIUS
+    let diameter = 2.0 * COLLISION_RADIUS;
+    stats.mean_free_path = 1.0 / (std::f32::consts::SQRT_2 * std::f32::consts::PI * diameter * diameter * stats.number_density);
+}
+
+// m/s, for the blade tip Mach number
+#[derive(Resource, Clone, Copy)]
+struct SpeedOfSound(f32);
+
+// Physical size of one simulation length unit. Masses already come out in kg from
+// fluid_density, so only lengths need scaling.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct SimulationScale {
+    length_m_per_unit: f32,
+}
+
+impl Default for SimulationScale {
+    fn default() -> Self {
+        SimulationScale { length_m_per_unit: 1.0 }
+    }
+}
+
+// Speed move_particles clamps every particle to, keeps runaway blade impulses in check
+#[derive(Resource, Clone, Copy)]
+struct MaxParticleSpeed(f32);
+
+// Particles slower than this are stopped, a crude small-scale viscous damping; 0.0 is off
+#[derive(Resource, Clone, Copy)]
+struct MinParticleSpeed(f32);
+
+// Clamps applied by MaxParticleSpeed in the last physics step
+#[derive(Resource, Default)]
+struct OverspeedDiagnostic {
+    count: u32,
+    max_speed_seen: f32,
+}
+
+// Clamps in one step beyond which the step is reported
+const OVERSPEED_REPORT_COUNT: u32 = 10;
+
+// max_particle_speed when it is not set and the fluid is not a gas
+const DEFAULT_MAX_PARTICLE_SPEED: f32 = 20.0;
+
+// Mass and momentum carried out through OpenOutflow walls, for mass-flux calculation
+#[derive(Resource, Default)]
+struct OutflowFlux {
+    mass: f32,
+    momentum: Vec3,
+    particles: u32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum GravityMode {
+    Uniform,
+    // pushes particles away from center, as in a centrifugal chamber
+    Radial { center: Vec3, strength: f32 },
+    None,
+}
+
+// Ambient flow the particles are pushed towards, e.g. axial inflow into the rotor.
+// Shear grows along +Y at gradient per metre, Turbulent adds Gaussian gusts that stay
+// correlated over cells of length_scale.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+enum WindProfile {
+    Uniform(Vec3),
+    Shear { direction: Vec3, gradient: f32 },
+    Turbulent { mean: Vec3, intensity: f32, length_scale: f32 },
+}
+
+impl Default for WindProfile {
+    fn default() -> Self {
+        WindProfile::Uniform(Vec3::ZERO)
+    }
+}
+
+impl WindProfile {
+    fn wind_at(&self, pos: Vec3) -> Vec3 {
+        match *self {
+            WindProfile::Uniform(wind) => wind,
+            WindProfile::Shear { direction, gradient } => direction * gradient * pos.y,
+            WindProfile::Turbulent { mean, intensity, length_scale } => {
+                // same cell, same gust
+                let cell = (pos / length_scale.max(f32::EPSILON)).floor().as_ivec3();
+                let seed = (cell.x as u64).wrapping_mul(73856093) ^ (cell.y as u64).wrapping_mul(19349663) ^ (cell.z as u64).wrapping_mul(83492791);
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let mut gaussian = || {
+                    // Box-Muller
+                    let (u1, u2): (f32, f32) = (rng.gen_range(f32::EPSILON..1.0), rng.gen());
+                    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+                };
+                mean + Vec3::new(gaussian(), gaussian(), gaussian()) * intensity
+            }
+        }
+    }
+
+    // axial speed through the rotor disk at the hub, the mean wind without gusts
+    fn inflow_speed(&self) -> f32 {
+        match *self {
+            WindProfile::Turbulent { mean, .. } => mean.y.abs(),
+            _ => self.wind_at(Vec3::ZERO).y.abs(),
+        }
+    }
+}
+
+// acceleration of Vec3::ZERO or GravityMode::None gives the weightless behaviour
+#[derive(Resource)]
+struct Gravity {
+    mode: GravityMode,
+    acceleration: Vec3,
+    scale: f32,
+}
+
+impl Gravity {
+    fn acceleration_at(&self, pos: Vec3) -> Vec3 {
+        match self.mode {
+            GravityMode::Uniform => self.acceleration * self.scale,
+            GravityMode::Radial { center, strength } => (pos - center).normalize_or_zero() * strength * self.scale,
+            GravityMode::None => Vec3::ZERO,
+        }
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity { mode: GravityMode::Uniform, acceleration: Vec3::new(0.0, -9.81, 0.0), scale: 1.0 }
+    }
+}
+
+// A family of pitches in one run. Serially it replaces the sweep; with parallel_propellers
+// each pitch gets its own rotor and particle box, all collected at once.
+#[derive(Clone, Serialize, Deserialize)]
+struct BatchMode {
+    pitches: Vec<f32>,
+    parallel_propellers: bool,
+}
+
+// Centre of the index-th batch domain, boxes a tenth of their size apart along X
+fn domain_offset(index: usize, bounds: &BoundingBox) -> Vec3 {
+    Vec3::X * index as f32 * bounds.size().x * 1.1
+}
+
+// Simulation constants, read from config.toml at startup. Missing fields keep their defaults.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SimConfig {
+    bounding_box: BoundingBox,
+    particle_count: usize,
+    pitch_start: f32,
+    pitch_end: f32,
+    pitch_step: f32,
+    // explicit, increasing sweep; when set pitch_start/end/step are ignored
+    pitch_values: Option<Vec<f32>>,
+    // overrides the sweep and pitch_values when set
+    batch_mode: Option<BatchMode>,
+    pitch_control: PitchControl,
+    pitch_optimizer: PitchOptimizer,
+    thrust_controller: ThrustController,
+    // CSV results file
+    output_path: String,
+    trial_count: u32,
+    trial_duration: f32,
+    start_prop_velocity: f32,
+    power_input: f32,
+    // of each blade, unless material is set
+    propeller_mass: f32,
+    // Young's modulus of the blades, Pa, unless material is set
+    elastic_modulus: f32,
+    material: Option<PropellerMaterial>,
+    hub_geometry: HubGeometry,
+    fluid_density: f32,
+    // air at this ISA altitude in place of fluid_density
+    altitude_m: Option<f32>,
+    particle_radius: f32,
+    particle_init: ParticleInitConfig,
+    gravity_mode: GravityMode,
+    gravity_scale: f32,
+    // no window, renderer or gizmos; also enabled with --headless
+    headless: bool,
+    // single byte separating the CSV columns
+    csv_delimiter: char,
+    output_format: OutputFormat,
+    // every result file is written under this directory, created if missing
+    output_dir: std::path::PathBuf,
+    particle_color_mode: ParticleColorMode,
+    // seconds per physics step, independent of the render frame rate
+    fixed_timestep: f32,
+    // simulated seconds per real second at startup, 0.01 to 10
+    time_scale: f32,
+    // spin-up before each trial, no thrust is recorded
+    warmup_duration: f32,
+    // seconds at the start of the warmup over which ConstantPower ramps up from zero, 0 for none
+    startup_duration: f32,
+    restitution: f32,
+    blade_restitution: f32,
+    // J/(kg K); reports each trial's collision heating as a temperature rise when set
+    heat_capacity: Option<f32>,
+    wind_profile: WindProfile,
+    // write every blade strike to collisions_{pitch}_{trial}.csv
+    log_collisions: bool,
+    // time the heaviest physics systems, logging a table every 60 frames
+    benchmark: bool,
+    // None drives the rotor with ConstantPower(power_input)
+    rotor_governor: Option<RotorGovernor>,
+    // None keeps every blade at its sweep pitch
+    cyclic_pitch: Option<CyclicPitch>,
+    // None sheds no tip vortex
+    wake_vortex: Option<WakeVortexConfig>,
+    // None adds no downwash body force
+    downwash: Option<Downwash>,
+    // None leaves the rotors unducted
+    duct: Option<DuctGeometry>,
+    physics_mode: PhysicsMode,
+    solver_mode: SolverMode,
+    // Pa s, and whether it drags on the particles
+    viscosity: f32,
+    viscous_drag: bool,
+    // None holds viscosity fixed
+    temperature_dependent_viscosity: Option<TemperatureDependentViscosity>,
+    // radial strips per blade in the BladeElement mode
+    blade_elements: usize,
+    boundary_conditions: [BoundaryCondition; 3],
+    emitters: Vec<ParticleEmitter>,
+    // particles the ParticlePool holds beyond particle_count, emitters stop once it runs dry
+    max_particles: usize,
+    propeller_array: PropellerArray,
+    efficiency_plot: EfficiencyPlotConfig,
+    ground_plane: GroundPlane,
+    geometry: PropellerGeometry,
+    // fixes every random draw so runs repeat; None seeds from entropy
+    seed: Option<u64>,
+    vtk_export: VtkExportConfig,
+    density_regulator: DensityRegulator,
+    // fraction of the total kinetic energy a step's budget may miss by before it is reported
+    energy_tolerance: f32,
+    // also evaluates blade element theory on the struck blades each step to compare power coefficients
+    energy_bem_comparison: bool,
+    // downward velocity a respawned struck particle keeps per unit impulse over its mass
+    slipstream_gain: f32,
+    // None derives it from the initial velocity distribution
+    max_particle_speed: Option<f32>,
+    min_particle_speed: f32,
+    // m/s
+    speed_of_sound: f32,
+    simulation_scale: SimulationScale,
+    // non-zero tilts every disk from horizontal towards vertical about tilt_axis
+    tilt_rate_deg_per_second: f32,
+    tilt_axis: Vec3,
+    imbalance: PropellerImbalance,
+    // radial sections of the blade strike heatmap
+    heatmap_bins: usize,
+    // polar cells of the disk plane density field
+    polar_ring_bins: usize,
+    polar_angle_bins: usize,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            bounding_box: BoundingBox::default(),
+            particle_count: 400,
+            pitch_start: 45.0,
+            pitch_end: 85.0,
+            pitch_step: 5.0,
+            pitch_values: None,
+            batch_mode: None,
+            pitch_control: PitchControl::Sweep,
+            pitch_optimizer: PitchOptimizer::default(),
+            thrust_controller: ThrustController::default(),
+            output_path: "output.csv".to_string(),
+            trial_count: 8,
+            trial_duration: 10.0,
+            start_prop_velocity: 0.0,
+            power_input: 50000.0,
+            propeller_mass: 5.0,
+            elastic_modulus: 70.0e9,
+            material: None,
+            hub_geometry: HubGeometry::default(),
+            fluid_density: FluidDensity::AIR_SEA_LEVEL.density_kg_per_m3,
+            altitude_m: None,
+            particle_radius: FluidDensity::AIR_SEA_LEVEL.particle_radius,
+            particle_init: ParticleInitConfig::default(),
+            gravity_mode: GravityMode::Uniform,
+            gravity_scale: 1.0,
+            headless: false,
+            csv_delimiter: ',',
+            output_format: OutputFormat::Csv,
+            output_dir: std::path::PathBuf::from("."),
+            particle_color_mode: ParticleColorMode::Uniform(Color::rgb(1.0, 0.0, 0.0)), // Red particles
+            fixed_timestep: 1.0 / 120.0,
+            time_scale: 1.0,
+            warmup_duration: 2.0,
+            startup_duration: 2.0,
+            restitution: 1.0,
+            blade_restitution: 1.0,
+            heat_capacity: None,
+            wind_profile: WindProfile::default(),
+            log_collisions: false,
+            benchmark: false,
+            rotor_governor: None,
+            cyclic_pitch: None,
+            wake_vortex: None,
+            downwash: None,
+            duct: None,
+            physics_mode: PhysicsMode::MolecularDynamics,
+            solver_mode: SolverMode::ForwardEuler,
+            viscosity: Viscosity::AIR_SEA_LEVEL.0,
+            viscous_drag: false,
+            temperature_dependent_viscosity: None,
+            blade_elements: 10,
+            boundary_conditions: [BoundaryCondition::Reflect; 3],
+            emitters: Vec::new(),
+            max_particles: 0,
+            propeller_array: PropellerArray::default(),
+            efficiency_plot: EfficiencyPlotConfig::default(),
+            ground_plane: GroundPlane::default(),
+            geometry: PropellerGeometry::default(),
+            seed: None,
+            slipstream_gain: 1.0,
+            max_particle_speed: None,
+            min_particle_speed: 0.0,
+            speed_of_sound: 343.0,
+            simulation_scale: SimulationScale::default(),
+            tilt_rate_deg_per_second: 0.0,
+            tilt_axis: Vec3::X,
+            imbalance: PropellerImbalance::default(),
+            heatmap_bins: 10,
+            polar_ring_bins: 4,
+            polar_angle_bins: 24,
+            energy_tolerance: 0.05,
+            energy_bem_comparison: false,
+            vtk_export: VtkExportConfig::default(),
+            density_regulator: DensityRegulator::default(),
+        }
+    }
+}
+
+// One-shot overrides of config.toml
+#[derive(Parser)]
+#[command(about = "Particle-based propeller pitch sweep")]
+struct Cli {
+    /// First pitch of the sweep, degrees
+    #[arg(long)]
+    pitch_start: Option<f32>,
+    /// Pitch the sweep stops at, degrees. Equal to --pitch-start runs that one pitch
+    #[arg(long)]
+    pitch_end: Option<f32>,
+    /// Pitch increment between sweep points, degrees
+    #[arg(long)]
+    pitch_step: Option<f32>,
+    /// Number of fluid particles
+    #[arg(long)]
+    particle_count: Option<usize>,
+    /// CSV results file
+    #[arg(long)]
+    output: Option<String>,
+    /// Run without a window, renderer or gizmos
+    #[arg(long)]
+    headless: bool,
+    /// Fluid density, kg/m^3
+    #[arg(long)]
+    fluid_density: Option<f32>,
+    /// Print the resolved configuration as TOML and exit
+    #[arg(long)]
+    dry_run: bool,
+    /// Seed for every random draw, making runs reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Blade material: carbon-fiber, aluminum, wood, or name:density:modulus in kg/m^3 and Pa
+    #[arg(long, value_parser = PropellerMaterial::parse)]
+    material: Option<PropellerMaterial>,
+    /// Run the sweep headless, then print the pitch giving this thrust per rotor, N
+    #[arg(long)]
+    find_pitch_for_thrust: Option<f32>,
+    /// Rerun the first pitch with each design parameter raised by this fraction and write dT/d(param) to sensitivity.csv.
+    /// The speed row is rpm with a ConstantRPM rotor_governor, otherwise it is the shaft power, power_input
+    #[arg(long)]
+    sensitivity: Option<f32>,
+    /// Balance a counter-rotating Tandem pair to this net torque at every pitch, N m, and write torque_balance.csv
+    #[arg(long)]
+    torque_balance: Option<f32>,
+}
+
+impl Cli {
+    fn apply(&self, config: &mut SimConfig) {
+        // an explicit range on the command line wins over a pitch_values list in the file
+        if self.pitch_start.is_some() || self.pitch_end.is_some() || self.pitch_step.is_some() {
+            config.pitch_values = None;
+        }
+        if let Some(pitch_start) = self.pitch_start {
+            config.pitch_start = pitch_start;
+        }
+        if let Some(pitch_end) = self.pitch_end {
+            config.pitch_end = pitch_end;
+        }
+        if let Some(pitch_step) = self.pitch_step {
+            config.pitch_step = pitch_step;
+        }
+        if let Some(particle_count) = self.particle_count {
+            config.particle_count = particle_count;
+        }
+        if let Some(output) = &self.output {
+            config.output_path = output.clone();
+        }
+        if let Some(fluid_density) = self.fluid_density {
+            config.fluid_density = fluid_density;
+        }
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if let Some(material) = &self.material {
+            config.material = Some(material.clone());
+        }
+        config.headless |= self.headless;
+    }
+}
+
+// Seed the run was started with, None when drawn from entropy
+#[derive(Resource, Clone, Copy)]
+struct RandomSeed(Option<u64>);
+
+// Source of every random draw in the simulation, seeded from RandomSeed
+#[derive(Resource)]
+struct SimRng(rand::rngs::StdRng);
+
+impl SimRng {
+    fn new(seed: RandomSeed) -> Self {
+        match seed.0 {
+            Some(seed) => SimRng(rand::rngs::StdRng::seed_from_u64(seed)),
+            None => SimRng(rand::rngs::StdRng::from_entropy()),
+        }
+    }
+}
+
+// Trials averaged per pitch, at least one
+#[derive(Resource, Clone, Copy)]
+struct TrialCount(u32);
+
+impl SimConfig {
+    // the first setting out of range, as a message for the user. A NaN setting fails every check.
+    #[allow(clippy::neg_cmp_op_on_partial_ord)]
+    fn validate(&self) -> Result<(), String> {
+        macro_rules! check {
+            ($condition:expr, $($message:tt)+) => {
+                if !$condition {
+                    return Err(format!($($message)+));
+                }
+            };
+        }
+        match &self.pitch_values {
+            Some(values) => {
+                check!(!values.is_empty(), "pitch_values must list at least one pitch");
+                check!(values.windows(2).all(|w| w[1] > w[0]), "pitch_values must be strictly increasing, got {:?}", values);
+            }
+            None => {
+                let span = self.pitch_end - self.pitch_start;
+                check!(self.pitch_step > 0.0 && span >= 0.0, "pitch_end ({}) must be above pitch_start ({}) with a positive pitch_step ({})", self.pitch_end, self.pitch_start, self.pitch_step);
+                let steps = span / self.pitch_step;
+                check!((steps - steps.round()).abs() < 1e-3, "pitch_end - pitch_start ({}) must be a whole multiple of pitch_step ({})", span, self.pitch_step);
+            }
+        }
+        if let Some(batch) = &self.batch_mode {
+            check!(!batch.pitches.is_empty(), "batch_mode.pitches must list at least one pitch");
+            check!(batch.parallel_propellers || batch.pitches.windows(2).all(|w| w[1] > w[0]), "serial batch_mode.pitches must be strictly increasing, got {:?}", batch.pitches);
+            if batch.parallel_propellers {
+                // these act on the first domain only and would skew its rotor against the rest
+                check!(self.emitters.is_empty(), "emitters are not supported with a parallel batch_mode");
+                check!(!self.boundary_conditions.iter().any(|c| matches!(c, BoundaryCondition::WindTunnel { .. })), "WindTunnel boundaries are not supported with a parallel batch_mode");
+                check!(!self.density_regulator.enabled, "density_regulator is not supported with a parallel batch_mode");
+            }
+        }
+        if self.pitch_optimizer.mode == OptimizerMode::GoldenSection {
+            let optimizer = &self.pitch_optimizer;
+            check!(optimizer.bracket_low < optimizer.bracket_high, "pitch_optimizer bracket_low must be below bracket_high, got [{}, {}]", optimizer.bracket_low, optimizer.bracket_high);
+            check!(optimizer.tolerance > 0.0, "pitch_optimizer.tolerance must be positive, got {}", optimizer.tolerance);
+            check!(self.pitch_control == PitchControl::Sweep && self.batch_mode.is_none(), "the golden-section optimizer picks the pitches itself, use pitch_control = \"sweep\" without batch_mode");
+        }
+        check!(self.bounding_box.half_extent.min_element() > 0.0, "bounding_box.half_extent must be positive on every axis, got {}", self.bounding_box.half_extent);
+        check!(self.imbalance.imbalance_mass >= 0.0 && self.imbalance.imbalance_radius >= 0.0 && self.imbalance.compliance >= 0.0, "imbalance mass, radius and compliance must not be negative");
+        // the csv writer takes one byte
+        check!(self.csv_delimiter.is_ascii(), "csv_delimiter must be an ASCII character, got {:?}", self.csv_delimiter);
+        check!(self.efficiency_plot.width > 0 && self.efficiency_plot.height > 0, "efficiency_plot width and height must be positive");
+        check!(self.trial_count >= 1, "trial_count must be at least 1, got {}", self.trial_count);
+        check!(self.elastic_modulus > 0.0, "elastic_modulus must be positive, got {}", self.elastic_modulus);
+        check!(self.propeller_array.count >= 1, "propeller_array.count must be at least 1, got {}", self.propeller_array.count);
+        if let Some(ratio) = self.propeller_array.alternate_rpm_ratio {
+            check!(ratio.is_finite() && ratio > 0.0, "propeller_array.alternate_rpm_ratio must be positive, got {}", ratio);
+            check!(matches!(self.rotor_governor, Some(RotorGovernor::ConstantRPM { .. })), "propeller_array.alternate_rpm_ratio needs a ConstantRPM rotor_governor");
+        }
+        check!(self.blade_elements >= 1, "blade_elements must be at least 1, got {}", self.blade_elements);
+        if let Some(wake) = &self.wake_vortex {
+            check!(wake.helix_pitch > 0.0, "wake_vortex.helix_pitch must be positive, got {}", wake.helix_pitch);
+        }
+        if let Some(downwash) = &self.downwash {
+            check!(downwash.radius > 0.0 && downwash.decay_rate >= 0.0, "downwash radius must be positive and decay_rate non-negative");
+        }
+        if let Some(duct) = &self.duct {
+            check!(duct.inner_radius > self.geometry.span, "duct.inner_radius ({}) must clear the blade span ({})", duct.inner_radius, self.geometry.span);
+            check!(duct.outer_radius > duct.inner_radius && duct.length > 0.0, "duct.outer_radius must exceed inner_radius and length must be positive");
+        }
+        check!(self.heatmap_bins >= 1, "heatmap_bins must be at least 1, got {}", self.heatmap_bins);
+        check!(self.polar_ring_bins >= 1 && self.polar_angle_bins >= 1, "polar_ring_bins and polar_angle_bins must be at least 1");
+        check!(self.viscosity >= 0.0, "viscosity must not be negative, got {}", self.viscosity);
+        check!((0.0..=1.0).contains(&self.restitution), "restitution must be within 0.0..=1.0, got {}", self.restitution);
+        check!((0.0..=1.0).contains(&self.blade_restitution), "blade_restitution must be within 0.0..=1.0, got {}", self.blade_restitution);
+        check!(self.geometry.span > 0.0 && self.geometry.chord > 0.0 && self.geometry.thickness > 0.0, "blade span, chord and thickness must be positive");
+        check!(self.geometry.taper_ratio > 0.0, "taper_ratio must be positive, got {}", self.geometry.taper_ratio);
+        check!(self.geometry.sweep_angle_deg.abs() < 60.0, "sweep_angle_deg must be within +-60, got {}", self.geometry.sweep_angle_deg);
+        check!(self.tilt_axis.length_squared() > 0.0, "tilt_axis must be non-zero");
+        check!(self.fixed_timestep > 0.0, "fixed_timestep must be positive, got {}", self.fixed_timestep);
+        check!(self.startup_duration >= 0.0, "startup_duration must not be negative, got {}", self.startup_duration);
+        check!((TimeScale::MIN..=TimeScale::MAX).contains(&self.time_scale), "time_scale must be within {} to {}, got {}", TimeScale::MIN, TimeScale::MAX, self.time_scale);
+        check!(self.max_particle_speed() > self.min_particle_speed.max(0.0), "max_particle_speed ({}) must be above min_particle_speed ({}) and zero", self.max_particle_speed(), self.min_particle_speed);
+        match self.particle_init.velocity_distribution {
+            VelocityDist::Uniform { min, max } => check!(min < max, "uniform velocity min ({}) must be below max ({})", min, max),
+            VelocityDist::Gaussian { std_dev, .. } => check!(std_dev.is_finite() && std_dev >= 0.0, "velocity std_dev must be non-negative, got {}", std_dev),
+            VelocityDist::MaxwellBoltzmann { temperature, mass } => check!(temperature >= 0.0 && mass > 0.0, "Maxwell-Boltzmann temperature must be non-negative and mass positive"),
+        }
+        Ok(())
+    }
+
+    // pitch of the index-th sweep point, None once the sweep is done. Uniform sweeps stop
+    // before pitch_end, pitches are derived from the index so float error can't accumulate.
+    fn pitch_at(&self, index: u32) -> Option<f32> {
+        // later pitches come from the search as it narrows
+        if self.pitch_optimizer.mode == OptimizerMode::GoldenSection {
+            return (index == 0).then(|| GoldenSectionSearch::new(&self.pitch_optimizer).c);
+        }
+        if let Some(batch) = &self.batch_mode {
+            // a parallel batch is a single sweep point with every pitch on its own rotor
+            if batch.parallel_propellers {
+                return (index == 0).then_some(batch.pitches[0]);
+            }
+            return batch.pitches.get(index as usize).copied();
+        }
+        match &self.pitch_values {
+            Some(values) => values.get(index as usize).copied(),
+            None => {
+                // pitch_start == pitch_end runs that single pitch
+                if self.pitch_start == self.pitch_end {
+                    return (index == 0).then_some(self.pitch_start);
+                }
+                let pitch = self.pitch_start + index as f32 * self.pitch_step;
+                (pitch < self.pitch_end - self.pitch_step * 0.5).then_some(pitch)
+            }
+        }
+    }
+
+    // whole fixed steps in a duration, the phases are timed in steps
+    fn steps(&self, seconds: f32) -> u32 {
+        (seconds / self.fixed_timestep).round() as u32
+    }
+
+    fn fluid(&self) -> FluidDensity {
+        let density = self.altitude_m.map_or(self.fluid_density, air_density_at_altitude);
+        let mut fluid = FluidDensity { density_kg_per_m3: density, particle_radius: self.particle_radius, ..FluidDensity::AIR_SEA_LEVEL };
+        if let Some(heat_capacity) = self.heat_capacity {
+            fluid.specific_heat = heat_capacity;
+        }
+        if let VelocityDist::MaxwellBoltzmann { temperature, mass } = self.particle_init.velocity_distribution {
+            fluid.temperature_k = temperature;
+            fluid.molecular_mass_kg = mass;
+        }
+        fluid
+    }
+
+    // a gas starts ten thermal speeds clear of the clamp, so the initial distribution survives
+    fn max_particle_speed(&self) -> f32 {
+        self.max_particle_speed.unwrap_or_else(|| match self.particle_init.velocity_distribution {
+            VelocityDist::MaxwellBoltzmann { .. } => DEFAULT_MAX_PARTICLE_SPEED.max(10.0 * self.fluid().thermal_speed()),
+            _ => DEFAULT_MAX_PARTICLE_SPEED,
+        })
+    }
+
+    fn first_pitch(&self) -> f32 {
+        self.pitch_at(0).expect("validated sweep has a first pitch")
+    }
+
+    fn parallel_batch(&self) -> Option<&BatchMode> {
+        self.batch_mode.as_ref().filter(|batch| batch.parallel_propellers)
+    }
+
+    // starting pitch of the index-th rotor
+    fn rotor_pitch(&self, index: usize) -> f32 {
+        self.parallel_batch().map_or(self.first_pitch(), |batch| batch.pitches[index])
+    }
+
+    // a parallel batch lays one rotor out per domain in place of propeller_array
+    fn rotor_array(&self) -> PropellerArray {
+        match self.parallel_batch() {
+            Some(batch) => PropellerArray { count: batch.pitches.len() as u32, arrangement: ArrayArrangement::Row { spacing: domain_offset(1, &self.bounding_box).x }, ..self.propeller_array },
+            None => self.propeller_array,
+        }
+    }
+
+    fn domain_centers(&self) -> Vec<Vec3> {
+        let count = self.parallel_batch().map_or(1, |batch| batch.pitches.len());
+        (0..count).map(|i| domain_offset(i, &self.bounding_box)).collect()
+    }
+
+    // config.toml syntax, missing keys keep their defaults
+    pub fn from_toml(text: &str) -> Result<SimConfig, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    // falls back to the defaults when the file is absent
+    fn load(path: &str) -> Result<SimConfig, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => SimConfig::from_toml(&text).map_err(|err| format!("{}: {}", path, err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                println!("No {} found, using default configuration", path);
+                Ok(SimConfig::default())
+            }
+            Err(err) => Err(format!("{}: {}", path, err)),
+        }
+    }
+}
+
+// Gizmo lines queued by the physics systems, only present when rendering. The last field is
+// the line's age in real seconds.
+#[derive(Resource, Default)]
+struct DebugLines(Vec<(Vec3, Vec3, Color, f32)>);
+
+// Real seconds a collision line stays on screen at 1x
+const DEBUG_LINE_LIFETIME: f32 = 0.5;
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy)]
+enum ParticleColorMode {
+    Uniform(Color),
+    SpeedHeatmap { min_speed: f32, max_speed: f32 },
+    // white when a blade has just struck the particle, fading to blue over fade_seconds
+    CollisionRecency { fade_seconds: f32 },
+}
+
+impl ParticleColorMode {
+    fn color_for(&self, speed: f32) -> Color {
+        match *self {
+            ParticleColorMode::Uniform(color) => color,
+            ParticleColorMode::SpeedHeatmap { min_speed, max_speed } => {
+                // blue -> green -> yellow -> red
+                let stops = [Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)];
+                let t = ((speed - min_speed) / (max_speed - min_speed)).clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+                let i = (t as usize).min(stops.len() - 2);
+                let rgb = stops[i].lerp(stops[i + 1], t - i as f32);
+                Color::rgb(rgb.x, rgb.y, rgb.z)
+            }
+            // never struck
+            ParticleColorMode::CollisionRecency { .. } => Color::GRAY,
+        }
+    }
+}
+
+// Elapsed time of the last blade strike and the last particle-particle collision on a
+// particle, None if it never had one
+#[derive(Component, Default)]
+struct LastCollisionTime {
+    blade: Option<f32>,
+    particle: Option<f32>,
+}
+
+// Collisions a particle took part in this trial. An even spread of blade_hits over the
+// particles means the blades reach the whole fluid, a long tail that a few are hit over and over.
+#[derive(Component, Default)]
+struct CollisionCount {
+    blade_hits: u32,
+    particle_hits: u32,
+}
+
+// (mean, max, standard deviation) of blade_hits over the particles
+fn blade_hit_statistics<'a>(counts: impl Iterator<Item = &'a CollisionCount>) -> Vec3 {
+    let hits: Vec<f32> = counts.map(|count| count.blade_hits as f32).collect();
+    if hits.is_empty() {
+        return Vec3::ZERO;
+    }
+    let n = hits.len() as f32;
+    let mean = hits.iter().sum::<f32>() / n;
+    let max = hits.iter().copied().fold(0.0, f32::max);
+    let std_dev = (hits.iter().map(|h| (h - mean).powi(2)).sum::<f32>() / n).sqrt();
+    Vec3::new(mean, max, std_dev)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+enum OutputFormat {
+    Csv,
+    Json,
+    Both,
+}
+
+// A completed pitch in the JSON Lines output
+#[derive(Serialize)]
+struct JsonRecord {
+    pitch_deg: f32,
+    trials: Vec<f32>,
+    mean_impulse: f32,
+}
+
+#[derive(Resource, Clone)]
+struct CsvOutputConfig {
+    file_path: String,
+    write_header: bool,
+    delimiter: char,
+}
+
+impl Default for CsvOutputConfig {
+    fn default() -> Self {
+        CsvOutputConfig { file_path: "output.csv".to_string(), write_header: true, delimiter: ',' }
+    }
+}
+
+// Where the DataLoggerPlugin writes completed pitches and result files, every file name
+// is taken relative to output_dir
+#[derive(Resource, Clone)]
+struct DataLoggerConfig {
+    output_dir: std::path::PathBuf,
+    format: OutputFormat,
+}
+
+impl Default for DataLoggerConfig {
+    fn default() -> Self {
+        DataLoggerConfig { output_dir: std::path::PathBuf::from("."), format: OutputFormat::Csv }
+    }
+}
+
+// Periodic ParaView snapshots of the particles and blades, one .vtu file per export
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct VtkExportConfig {
+    enabled: bool,
+    interval_seconds: f32,
+    output_dir: std::path::PathBuf,
+}
+
+impl Default for VtkExportConfig {
+    fn default() -> Self {
+        VtkExportConfig { enabled: false, interval_seconds: 0.5, output_dir: std::path::PathBuf::from("vtk") }
+    }
+}
+
+// A completed pitch, columns are the named values after the trials
+struct LogRecord {
+    pitch: f32,
+    trials: Vec<f32>,
+    average: f32,
+    columns: Vec<(String, f32)>,
+}
+
+// A result file written on the logger thread, given the output directory
+type LogJob = Box<dyn FnOnce(&std::path::Path) -> Result<(), Box<dyn Error>> + Send>;
+
+enum LogMessage {
+    Record(LogRecord),
+    // what the file holds, for the error message, and how to write it
+    File(&'static str, LogJob),
+    // answered once every record sent before it is on disk
+    Flush(std::sync::mpsc::Sender<()>),
+}
+
+// Sending half of the logger thread's channel
+#[derive(Resource)]
+struct DataLogger {
+    sender: std::sync::mpsc::Sender<LogMessage>,
+}
+
+impl DataLogger {
+    fn send(&self, record: LogRecord) {
+        if self.sender.send(LogMessage::Record(record)).is_err() {
+            eprintln!("Data logger thread has stopped, record dropped");
+        }
+    }
+
+    // every result file goes through here, so it lands in output_dir in order with the records
+    fn write_file(&self, what: &'static str, job: impl FnOnce(&std::path::Path) -> Result<(), Box<dyn Error>> + Send + 'static) {
+        if self.sender.send(LogMessage::File(what, Box::new(job))).is_err() {
+            eprintln!("Data logger thread has stopped, {} dropped", what);
+        }
+    }
+
+    // blocks until the logger thread has written everything queued so far
+    fn flush(&self) {
+        let (ack_sender, ack) = std::sync::mpsc::channel();
+        if self.sender.send(LogMessage::Flush(ack_sender)).is_ok() {
+            let _ = ack.recv();
+        }
+    }
+}
+
+const BENCHMARK_PATH: &str = "benchmark.csv";
+
+// Mean and variance of a stream of samples, updated one at a time (Welford)
+#[derive(Default, Clone, Copy)]
+struct RunningStats {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    fn push(&mut self, sample: f64) {
+        self.count += 1;
+        let delta = sample - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (sample - self.mean);
+    }
+
+    fn std_dev(&self) -> f64 {
+        if self.count < 2 { 0.0 } else { (self.m2 / (self.count - 1) as f64).sqrt() }
+    }
+}
+
+// Microseconds each timed system spends per frame, over every frame it ran in. A system
+// running several times a frame, for every fixed step or substep, is summed over them.
+#[derive(Resource, Default)]
+struct BenchmarkStats {
+    system_times: HashMap<&'static str, RunningStats>,
+    started: HashMap<&'static str, Instant>,
+    frame_times: HashMap<&'static str, f64>,
+    frames: u32,
+    written: bool,
+}
+
+impl BenchmarkStats {
+    fn table(&self) -> Vec<(&'static str, RunningStats)> {
+        BenchmarkPlugin::SYSTEMS.iter().filter_map(|name| self.system_times.get(name).map(|stats| (*name, *stats))).collect()
+    }
+}
+
+// Brackets blade_collisions, compare_particles, move_particles, wall_collisions and
+// update_rectangle_rotation with timing systems. The physics schedules run single threaded
+// while benchmarking so nothing else runs alongside a timed system.
+struct BenchmarkPlugin {
+    // write benchmark.csv at the end of the first trial
+    write_csv: bool,
+}
+
+impl BenchmarkPlugin {
+    const SYSTEMS: [&'static str; 5] = ["blade_collisions", "compare_particles", "move_particles", "wall_collisions", "update_rectangle_rotation"];
+    // frames between printed tables
+    const REPORT_EVERY: u32 = 60;
+}
+
+impl Plugin for BenchmarkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BenchmarkStats>()
+            .edit_schedule(FixedUpdate, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            })
+            .edit_schedule(PropellerSubstep, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            })
+            .add_systems(FixedUpdate, (
+                benchmark_begin("move_particles").before(move_particles),
+                benchmark_end("move_particles").after(move_particles),
+                benchmark_begin("wall_collisions").before(wall_collisions),
+                benchmark_end("wall_collisions").after(wall_collisions),
+                benchmark_begin("compare_particles").before(compare_particles),
+                benchmark_end("compare_particles").after(compare_particles),
+            ).in_set(PhysicsSet))
+            .add_systems(PropellerSubstep, (
+                benchmark_begin("update_rectangle_rotation").before(update_rectangle_rotation),
+                benchmark_end("update_rectangle_rotation").after(update_rectangle_rotation),
+                benchmark_begin("blade_collisions").before(blade_collisions),
+                benchmark_end("blade_collisions").after(blade_collisions),
+            ))
+            .add_systems(Last, report_benchmark);
+        if self.write_csv {
+            app.add_systems(Last, write_benchmark.after(report_benchmark));
+        }
+    }
+}
+
+fn benchmark_begin(name: &'static str) -> impl FnMut(ResMut<BenchmarkStats>) {
+    move |mut stats: ResMut<BenchmarkStats>| {
+        stats.started.insert(name, Instant::now());
+    }
+}
+
+fn benchmark_end(name: &'static str) -> impl FnMut(ResMut<BenchmarkStats>) {
+    move |mut stats: ResMut<BenchmarkStats>| {
+        if let Some(start) = stats.started.remove(name) {
+            *stats.frame_times.entry(name).or_default() += start.elapsed().as_secs_f64() * 1.0e6;
+        }
+    }
+}
+
+// Folds this frame's times into the running stats and logs the table every REPORT_EVERY frames
+fn report_benchmark(mut stats: ResMut<BenchmarkStats>) {
+    let frame_times: Vec<(&'static str, f64)> = stats.frame_times.drain().collect();
+    for (name, micros) in frame_times {
+        stats.system_times.entry(name).or_default().push(micros);
+    }
+    stats.frames += 1;
+    if !stats.frames.is_multiple_of(BenchmarkPlugin::REPORT_EVERY) {
+        return;
+    }
+    info!("{:<28} {:>24}", "system", "us per frame");
+    for (name, times) in stats.table() {
+        info!("{:<28} {:>12.1} +- {:<9.1}", name, times.mean, times.std_dev());
+    }
+}
+
+fn write_benchmark(mut stats: ResMut<BenchmarkStats>, state: Res<SimulationState>, logger: Res<DataLogger>) {
+    if stats.written || (state.trial == 0 && state.pitch_index == 0) {
+        return;
+    }
+    stats.written = true;
+    let table = stats.table();
+    logger.write_file("benchmark CSV", move |dir| write_benchmark_csv(&dir.join(BENCHMARK_PATH), &table));
+}
+
+fn write_benchmark_csv(file_path: &std::path::Path, table: &[(&'static str, RunningStats)]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(file_path)?;
+    wtr.write_record(["system", "mean_us", "std_dev_us", "frames"])?;
+    for (name, times) in table {
+        wtr.write_record(&[name.to_string(), times.mean.to_string(), times.std_dev().to_string(), times.count.to_string()])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Moves result file I/O onto its own thread so writes never stall the schedule.
+// Reads DataLoggerConfig and CsvOutputConfig, so add it after inserting them.
+struct DataLoggerPlugin;
+
+impl Plugin for DataLoggerPlugin {
+    fn build(&self, app: &mut App) {
+        let config = app.world.get_resource::<DataLoggerConfig>().cloned().unwrap_or_default();
+        let mut csv = app.world.get_resource::<CsvOutputConfig>().cloned().unwrap_or_default();
+        csv.file_path = config.output_dir.join(&csv.file_path).to_string_lossy().into_owned();
+        let json_path = config.output_dir.join("output.jsonl").to_string_lossy().into_owned();
+        if let Err(err) = std::fs::create_dir_all(&config.output_dir) {
+            eprintln!("Error creating output directory {}: {}", config.output_dir.display(), err);
+        }
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    LogMessage::Record(record) => write_log_record(&record, &config, &csv, &json_path),
+                    LogMessage::File(what, job) => {
+                        if let Err(err) = job(&config.output_dir) {
+                            eprintln!("Error writing {}: {}", what, err);
+                        }
+                    }
+                    LogMessage::Flush(ack) => {
+                        let _ = ack.send(());
+                    }
+                }
+            }
+        });
+
+        app.insert_resource(DataLogger { sender }).add_systems(Last, flush_data_logger);
+    }
+}
+
+fn write_log_record(record: &LogRecord, config: &DataLoggerConfig, csv: &CsvOutputConfig, json_path: &str) {
+    if matches!(config.format, OutputFormat::Csv | OutputFormat::Both) {
+        let mut row = vec![record.pitch];
+        row.extend(record.trials.iter());
+        row.extend(record.columns.iter().map(|(_, value)| *value));
+        let names: Vec<&str> = record.columns.iter().map(|(name, _)| name.as_str()).collect();
+        if let Err(err) = append_to_csv(csv, &csv_header(record.trials.len() as u32, &names), &row) {
+            eprintln!("Error writing CSV: {}", err);
+        } else {
+            info!("Successful writing to CSV");
+        }
+    }
+    if matches!(config.format, OutputFormat::Json | OutputFormat::Both) {
+        let json = JsonRecord { pitch_deg: record.pitch, trials: record.trials.clone(), mean_impulse: record.average };
+        if let Err(err) = append_to_json(json_path, &json) {
+            eprintln!("Error writing JSON: {}", err);
+        } else {
+            info!("Successful writing to JSON");
+        }
+    }
+}
+
+fn flush_data_logger(mut exit: EventReader<AppExit>, logger: Res<DataLogger>) {
+    if exit.read().next().is_some() {
+        logger.flush();
+    }
+}
+
+// Efficiency and CT against pitch, drawn once the sweep finishes
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct EfficiencyPlotConfig {
+    file_path: String,
+    width: u32,
+    height: u32,
+}
+
+impl Default for EfficiencyPlotConfig {
+    fn default() -> Self {
+        EfficiencyPlotConfig { file_path: "efficiency.svg".to_string(), width: 800, height: 600 }
+    }
+}
+
+// Systems that advance the simulation, skipped while paused
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct PhysicsSet;
+
+// Each trial warms up so the startup transient stays out of the data, collects for
+// trial_duration, then resets. Driven by transition_phase, the controller handles Resetting.
+// Both count fixed steps taken, so a phase lasts the same number of steps at any frame rate.
+#[derive(Resource, Clone, Copy)]
+enum SimulationPhase {
+    Warmup { steps: u32 },
+    Collecting { steps: u32 },
+    Resetting,
+}
+
+// Linear power ramp from zero over the first startup_duration of each warmup, so the rotor
+// spins up from rest as a motor would instead of taking the full power at once
+#[derive(Resource, Clone, Copy)]
+struct PropellerStartup {
+    startup_duration: f32,
+    // seconds per fixed step, warmup is counted in steps
+    timestep: f32,
+}
+
+impl PropellerStartup {
+    fn power_fraction(&self, phase: &SimulationPhase) -> f32 {
+        match *phase {
+            SimulationPhase::Warmup { steps } if self.startup_duration > 0.0 => (steps as f32 * self.timestep / self.startup_duration).clamp(0.0, 1.0),
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct SimulationPaused(bool);
+
+// Simulated seconds per real second, applied as the speed of virtual time. Physics keeps its
+// fixed_timestep and runs more or fewer steps per frame, and everything reading Time in Update
+// (the trial timer included) sees scaled seconds, so trial_duration stays simulation time.
+#[derive(Resource, Clone, Copy)]
+struct TimeScale(f32);
+
+impl TimeScale {
+    const MIN: f32 = 0.01;
+    const MAX: f32 = 10.0;
+    const STEP: f32 = 0.1;
+
+    fn new(scale: f32) -> Self {
+        TimeScale(scale.clamp(Self::MIN, Self::MAX))
+    }
+}
+
+// advance one fixed physics step while paused, cleared once that step has run
+#[derive(Resource, Default)]
+struct SingleStep(bool);
+
+// Everything needed to redraw one fixed-timestep tick
+#[derive(Serialize, Deserialize)]
+struct FrameSnapshot {
+    particles: Vec<(Entity, Transform, Particle)>,
+    hubs: Vec<(Entity, PropellerHub)>,
+    blades: Vec<(Entity, Transform)>,
+}
+
+// R toggles recording, P plays it back with physics stopped. Entities are stored as-is, so an
+// imported file only replays onto a run that spawned the same scene in the same order.
+#[derive(Resource, Default)]
+struct Recording {
+    frames: Vec<FrameSnapshot>,
+    recording: bool,
+    playing: bool,
+    cursor: usize,
+}
+
+const RECORDING_PATH: &str = "recording.bin";
+
+// Rolling window of total_vertical_impulse for the overlay
+#[cfg(feature = "ui")]
+#[derive(Resource)]
+struct ThrustDisplay {
+    window: usize,
+    samples: std::collections::VecDeque<f32>,
+}
+
+#[cfg(feature = "ui")]
+impl Default for ThrustDisplay {
+    fn default() -> Self {
+        ThrustDisplay { window: 60, samples: std::collections::VecDeque::new() }
+    }
+}
+
+// FluidTemperature over time for the overlay, (elapsed seconds, K)
+#[cfg(feature = "ui")]
+#[derive(Resource)]
+struct TemperatureDisplay {
+    max_samples: usize,
+    samples: std::collections::VecDeque<(f32, f32)>,
+}
+
+#[cfg(feature = "ui")]
+impl Default for TemperatureDisplay {
+    fn default() -> Self {
+        TemperatureDisplay { max_samples: 600, samples: std::collections::VecDeque::new() }
+    }
+}
+
+// Running mean thrust of the trial, drawn as a line chart beside the bounding box
+#[derive(Resource)]
+struct ThrustGraph {
+    history: std::collections::VecDeque<f32>,
+    max_samples: usize,
+    // lower left corner, the chart lies in the XY plane
+    display_position: Vec3,
+    display_size: Vec2,
+}
+
+impl Default for ThrustGraph {
+    fn default() -> Self {
+        ThrustGraph { history: std::collections::VecDeque::new(), max_samples: 300, display_position: Vec3::new(6.0, 1.0, 0.0), display_size: Vec2::new(4.0, 3.0) }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ShowVelocityVectors(bool);
+
+// Slice dot(pos, plane_normal) = plane_d drawn CFD-style, toggled with C and moved through the
+// volume with [ and ]. The default vertical slice through the hub shows the axial wake and swirl.
+#[derive(Resource)]
+struct CrossSectionView {
+    plane_normal: Vec3,
+    plane_d: f32,
+    enabled: bool,
+}
+
+impl Default for CrossSectionView {
+    fn default() -> Self {
+        CrossSectionView { plane_normal: Vec3::Z, plane_d: 0.0, enabled: false }
+    }
+}
+
+impl CrossSectionView {
+    // particles this close to the plane are in the slice
+    const THICKNESS: f32 = 0.1;
+    // plane_d change per [ or ] press
+    const SCROLL_STEP: f32 = 0.25;
+}
+
+// M hands the pitch to the arrow keys and stops the automatic sweep until pressed again
+#[derive(Resource, Default, PartialEq)]
+struct ManualControl(bool);
+
+// Time the current manual pitch has been held, the thrust rate is the impulse over it
+#[derive(Resource, Default)]
+struct ManualMeasurement {
+    elapsed: f32,
+    // blade pitches the sweep had when manual mode took over, put back when it ends
+    sweep_pitches: Vec<(Entity, f32)>,
+}
+
+const MANUAL_CSV_PATH: &str = "manual_measurements.csv";
+
+// Translucent swept disk of each rotor, shown alongside the velocity vectors
+#[derive(Component)]
+struct PropellerDiskMarker;
+
+#[derive(Resource)]
+struct ShowPropellerDisk(bool);
+
+impl Default for ShowPropellerDisk {
+    fn default() -> Self {
+        ShowPropellerDisk(true)
+    }
+}
+
+// Arrows are drawn for at most max_arrows particles within max_distance of the hub, nearest first
+#[derive(Resource)]
+struct VelocityVectorSettings {
+    scale: f32,
+    max_distance: f32,
+    max_arrows: usize,
+}
+
+impl Default for VelocityVectorSettings {
+    fn default() -> Self {
+        VelocityVectorSettings { scale: 0.2, max_distance: 5.0, max_arrows: 100 }
+    }
+}
+
+// Runs update_rectangle_rotation then blade_collisions once per substep
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct PropellerSubstep;
+
+#[derive(Resource)]
+struct SubstepCount(u32);
+
+impl Default for SubstepCount {
+    fn default() -> Self {
+        SubstepCount(4)
+    }
+}
+
+// Length of the current propeller substep
+#[derive(Resource, Default)]
+struct SubstepTime {
+    dt: f32,
+}
+
+// Dimensionless coefficients of the last completed pitch
+#[derive(Resource, Default, Clone, Copy)]
+struct PropellerCoefficients {
+    advance_ratio: f32,
+    ct: f32,
+    cp: f32,
+    figure_of_merit: f32,
+}
+
+impl PropellerCoefficients {
+    // n in rev/s, the diameter is the bounding box's X edge as a stand-in for the rotor
+    fn compute(thrust: f32, power: f32, n: f32, diameter: f32, density: f32, inflow_speed: f32) -> Self {
+        if n <= 0.0 {
+            return PropellerCoefficients::default();
+        }
+        let ct = thrust / (density * n.powi(2) * diameter.powi(4));
+        let cp = power / (density * n.powi(3) * diameter.powi(5));
+        PropellerCoefficients {
+            advance_ratio: inflow_speed / (n * diameter),
+            ct,
+            cp,
+            figure_of_merit: ct.abs().powf(1.5) / (std::f32::consts::SQRT_2 * cp),
+        }
+    }
+}
+
+// Rough acoustic figures of the last completed pitch: a simplified Gutin estimate of the
+// broadband level and the blade passage frequency
+#[derive(Resource, Default, Clone, Copy)]
+struct PropellerAcoustics {
+    spl_db: f32,
+    bpf_hz: f32,
+    tip_mach: f32,
+}
+
+impl PropellerAcoustics {
+    // n in rev/s, radius_m in metres and speed_of_sound in m/s
+    fn compute(thrust: f32, n: f32, radius_m: f32, disk_area: f32, density: f32, blades: u32, speed_of_sound: f32) -> Self {
+        let ratio = thrust * thrust / (density * density * disk_area * disk_area);
+        PropellerAcoustics {
+            spl_db: if ratio > 0.0 { 10.0 * ratio.log10() } else { 0.0 },
+            bpf_hz: n * blades as f32,
+            tip_mach: (std::f32::consts::TAU * n * radius_m).abs() / speed_of_sound,
+        }
+    }
+
+    fn warn_if_compressible(&self) {
+        // the model assumes incompressible flow at the tips
+        if self.tip_mach > 0.7 {
+            warn!("Tip Mach {:.2} is above 0.7, the acoustic estimate is not valid", self.tip_mach);
+        }
+    }
+}
+
+// Surface below the rotors, heights are world Y
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct GroundPlane {
+    height: f32,
+    enabled: bool,
+}
+
+impl Default for GroundPlane {
+    fn default() -> Self {
+        // floor of the bounding box
+        GroundPlane { height: -5.0, enabled: false }
+    }
+}
+
+// Cheeseman-Bennett thrust gain 1 / (1 - (R / 4h)^2), none above two rotor radii. It is
+// 16/15 (about 1.067) at h = R; 4/3 is reached at h = R / 2.
+fn compute_ground_effect_factor(prop_height: f32, rotor_radius: f32) -> f32 {
+    if prop_height > 2.0 * rotor_radius {
+        return 1.0;
+    }
+    if prop_height < 0.25 * rotor_radius {
+        warn!("Rotor is {} above the ground, under a quarter radius the ground effect model breaks down", prop_height);
+    }
+    // capped so the factor stays finite as h reaches R / 4
+    let ratio = (rotor_radius / (4.0 * prop_height.max(f32::EPSILON))).min(0.9);
+    1.0 / (1.0 - ratio * ratio)
+}
+
+// Blade dimensions, every blade of every rotor shares them
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct PropellerGeometry {
+    span: f32, // blade length from the hub
+    chord: f32, // at the root
+    thickness: f32,
+    taper_ratio: f32, // tip chord / root chord, 1 for a rectangular blade
+    sweep_angle_deg: f32, // positive sweeps the tip forward, into the rotation
+}
+
+impl Default for PropellerGeometry {
+    fn default() -> Self {
+        PropellerGeometry { span: 4.0, chord: 1.0, thickness: 0.05, taper_ratio: 1.0, sweep_angle_deg: 0.0 }
+    }
+}
+
+impl PropellerGeometry {
+    fn aspect_ratio(&self) -> f32 {
+        // span over mean chord
+        self.span / (self.chord * (1.0 + self.taper_ratio) / 2.0)
+    }
+
+    fn tip_chord(&self) -> f32 {
+        self.chord * self.taper_ratio
+    }
+
+    // linear from root to tip, radius measured from the hub
+    fn chord_at(&self, radius: f32) -> f32 {
+        let t = (radius / self.span).clamp(0.0, 1.0);
+        self.chord + (self.tip_chord() - self.chord) * t
+    }
+
+    // chordwise shift of the section at radius from the hub, towards the leading edge
+    fn sweep_offset(&self, radius: f32) -> f32 {
+        radius * self.sweep_angle_deg.to_radians().tan()
+    }
+
+    // root then tip corners in the blade's local XY plane, root at +x as placed by
+    // blade_transform and +y towards the direction of rotation
+    fn planform_corners(&self) -> [Vec3; 4] {
+        let (root, tip) = (self.span / 2.0, -self.span / 2.0);
+        let shift = self.sweep_offset(self.span);
+        [
+            Vec3::new(root, -self.chord / 2.0, 0.0),
+            Vec3::new(root, self.chord / 2.0, 0.0),
+            Vec3::new(tip, shift + self.tip_chord() / 2.0, 0.0),
+            Vec3::new(tip, shift - self.tip_chord() / 2.0, 0.0),
+        ]
+    }
+
+    // flat trapezoid, a parallelogram-like quad once swept
+    fn planform_mesh(&self) -> Mesh {
+        let corners = self.planform_corners().map(|corner| corner.to_array());
+        // both faces, so the blade shows from either side
+        let mut positions = corners.to_vec();
+        positions.extend(corners);
+        let mut normals = vec![[0.0, 0.0, 1.0]; 4];
+        normals.extend(vec![[0.0, 0.0, -1.0]; 4]);
+        let uvs = vec![[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]];
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, 0, 2, 3, 4, 6, 5, 4, 7, 6])));
+        mesh
+    }
+}
+
+// What the blades are made of, their mass follows from the geometry
+#[derive(Resource, Clone, Serialize, Deserialize)]
+struct PropellerMaterial {
+    name: std::borrow::Cow<'static, str>,
+    density_kg_per_m3: f32,
+    // Young's modulus, Pa
+    elastic_modulus: f32,
+}
+
+impl PropellerMaterial {
+    const CARBON_FIBER: PropellerMaterial = PropellerMaterial { name: std::borrow::Cow::Borrowed("carbon fiber"), density_kg_per_m3: 1600.0, elastic_modulus: 70.0e9 };
+    const ALUMINUM: PropellerMaterial = PropellerMaterial { name: std::borrow::Cow::Borrowed("aluminum"), density_kg_per_m3: 2700.0, elastic_modulus: 69.0e9 };
+    const WOOD: PropellerMaterial = PropellerMaterial { name: std::borrow::Cow::Borrowed("wood"), density_kg_per_m3: 600.0, elastic_modulus: 11.0e9 };
+
+    fn custom(name: &str, density_kg_per_m3: f32, elastic_modulus: f32) -> Self {
+        PropellerMaterial { name: std::borrow::Cow::Owned(name.to_string()), density_kg_per_m3, elastic_modulus }
+    }
+
+    // a preset name, or name:density:modulus for anything else
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "carbon-fiber" => Ok(PropellerMaterial::CARBON_FIBER),
+            "aluminum" => Ok(PropellerMaterial::ALUMINUM),
+            "wood" => Ok(PropellerMaterial::WOOD),
+            _ => {
+                let mut fields = text.split(':');
+                let (Some(name), Some(density), Some(modulus), None) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                    return Err(format!("unknown material {}, expected carbon-fiber, aluminum, wood or name:density:modulus", text));
+                };
+                let density = density.parse::<f32>().map_err(|err| format!("bad density in {}: {}", text, err))?;
+                let modulus = modulus.parse::<f32>().map_err(|err| format!("bad modulus in {}: {}", text, err))?;
+                Ok(PropellerMaterial::custom(name, density, modulus))
+            }
+        }
+    }
+
+    // a solid planform of the blade's mean chord
+    fn blade_mass(&self, geometry: &PropellerGeometry) -> f32 {
+        let mean_chord = geometry.chord * (1.0 + geometry.taper_ratio) / 2.0;
+        let mass = self.density_kg_per_m3 * mean_chord * geometry.span * geometry.thickness;
+        if !(0.001..=100.0).contains(&mass) {
+            warn!("Blade mass of {} kg in {} is outside 0.001 to 100 kg, check the geometry and material", mass, self.name);
+        }
+        mass
+    }
+}
+
+// Quantities derived from the geometry once at startup
+#[derive(Resource, Clone, Copy)]
+struct PropellerMetrics {
+    disk_area: f32, // swept by the blade tips
+}
+
+impl PropellerMetrics {
+    fn new(geometry: &PropellerGeometry) -> Self {
+        PropellerMetrics { disk_area: std::f32::consts::PI * geometry.span * geometry.span }
+    }
+}
+
+// Section lift and drag against angle of attack in degrees, tables sorted by angle
+#[derive(Resource)]
+struct NacaProfile {
+    cl_table: Vec<(f32, f32)>,
+    cd_table: Vec<(f32, f32)>,
+}
+
+impl Default for NacaProfile {
+    // NACA 0012 near Re = 1e6, flat-plate behaviour past stall
+    fn default() -> Self {
+        let cl = [(0.0, 0.0), (2.0, 0.22), (4.0, 0.44), (6.0, 0.66), (8.0, 0.86), (10.0, 1.05), (12.0, 1.2), (14.0, 1.3),
+            (16.0, 1.1), (18.0, 0.9), (20.0, 0.8), (30.0, 0.9), (45.0, 1.0), (60.0, 0.8), (90.0, 0.0)];
+        let cd = [(0.0, 0.006), (2.0, 0.0062), (4.0, 0.007), (6.0, 0.0085), (8.0, 0.0105), (10.0, 0.013), (12.0, 0.017), (14.0, 0.024),
+            (16.0, 0.06), (18.0, 0.12), (20.0, 0.18), (30.0, 0.5), (45.0, 1.0), (60.0, 1.5), (90.0, 1.9)];
+        // symmetric section: lift is odd in the angle, drag even
+        let mirror = |table: &[(f32, f32)], sign: f32| -> Vec<(f32, f32)> {
+            let mut full: Vec<(f32, f32)> = table.iter().rev().filter(|&&(aoa, _)| aoa > 0.0).map(|&(aoa, c)| (-aoa, sign * c)).collect();
+            full.extend(table.iter().copied());
+            full
+        };
+        NacaProfile { cl_table: mirror(&cl, -1.0), cd_table: mirror(&cd, 1.0) }
+    }
+}
+
+impl NacaProfile {
+    fn lookup_cl_cd(&self, aoa_deg: f32) -> (f32, f32) {
+        (interpolate_table(&self.cl_table, aoa_deg), interpolate_table(&self.cd_table, aoa_deg))
+    }
+}
+
+// Linear between neighbouring rows, clamped to the end rows outside the table
+fn interpolate_table(table: &[(f32, f32)], x: f32) -> f32 {
+    let (Some(&(x_first, y_first)), Some(&(x_last, y_last))) = (table.first(), table.last()) else {
+        return 0.0;
+    };
+    if x <= x_first {
+        return y_first;
+    }
+    if x >= x_last {
+        return y_last;
+    }
+    let i = table.partition_point(|&(x_row, _)| x_row <= x);
+    let (x0, y0) = table[i - 1];
+    let (x1, y1) = table[i];
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+// How the next pitch is chosen once every trial of one has run
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PitchControl {
+    // the fixed steps of pitch_start/end/step or pitch_values
+    Sweep,
+    // ThrustController corrections towards target_thrust
+    Pid,
+}
+
+// Gains in degrees of pitch per newton of thrust error, one step per finished pitch
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct ThrustController {
+    target_thrust: f32,
+    kp: f32,
+    ki: f32,
+    kd: f32,
+}
+
+impl Default for ThrustController {
+    fn default() -> Self {
+        ThrustController { target_thrust: 50.0, kp: 0.1, ki: 0.02, kd: 0.05 }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ThrustControllerState {
+    integral: f32,
+    previous_error: Option<f32>,
+}
+
+impl ThrustControllerState {
+    // pitch to run next given the mean thrust measured at pitch, kept within 0..=90 degrees
+    fn next_pitch(&mut self, controller: &ThrustController, measured_thrust: f32, pitch: f32) -> f32 {
+        let error = controller.target_thrust - measured_thrust;
+        let integral = self.integral + error;
+        let derivative = self.previous_error.map_or(0.0, |previous| error - previous);
+        self.previous_error = Some(error);
+        let unclamped = pitch + controller.kp * error + controller.ki * integral + controller.kd * derivative;
+        let next = unclamped.clamp(0.0, 90.0);
+        // no integrating while pinned at a pitch limit, so it comes off as soon as the error turns
+        if next == unclamped {
+            self.integral = integral;
+        }
+        next
+    }
+}
+
+// Whether the pitches come from the sweep or from a search for the best thrust per watt
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum OptimizerMode {
+    // the pitches pitch_control picks
+    LinearSweep,
+    GoldenSection,
+}
+
+// Golden-section search for the pitch maximising thrust / shaft power within
+// [bracket_low, bracket_high], stopping once the bracket is narrower than tolerance degrees
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+struct PitchOptimizer {
+    mode: OptimizerMode,
+    bracket_low: f32,
+    bracket_high: f32,
+    tolerance: f32,
+}
+
+impl Default for PitchOptimizer {
+    fn default() -> Self {
+        PitchOptimizer { mode: OptimizerMode::LinearSweep, bracket_low: 0.0, bracket_high: 90.0, tolerance: 1.0 }
+    }
+}
+
+const OPTIMIZER_LOG_PATH: &str = "optimizer_log.csv";
+
+// Bracket [a, b] and its interior points c < d at the golden ratio, with the efficiency found
+// at each. One of fc, fd is None while its point is being evaluated; c goes first.
+#[derive(Resource, Clone, Copy)]
+struct GoldenSectionSearch {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    fc: Option<f32>,
+    fd: Option<f32>,
+    evaluations: u32,
+}
+
+impl GoldenSectionSearch {
+    // 1 / golden ratio
+    const INV_PHI: f32 = 0.618_034;
+
+    fn new(optimizer: &PitchOptimizer) -> Self {
+        let (a, b) = (optimizer.bracket_low, optimizer.bracket_high);
+        GoldenSectionSearch { a, b, c: b - Self::INV_PHI * (b - a), d: a + Self::INV_PHI * (b - a), fc: None, fd: None, evaluations: 0 }
+    }
+
+    // Takes the efficiency at the pitch last handed out and returns the next pitch to try,
+    // None once the bracket is within tolerance
+    fn record(&mut self, efficiency: f32, tolerance: f32) -> Option<f32> {
+        self.evaluations += 1;
+        if self.fc.is_none() {
+            self.fc = Some(efficiency);
+        } else {
+            self.fd = Some(efficiency);
+        }
+        let (Some(fc), Some(fd)) = (self.fc, self.fd) else {
+            return Some(self.d);
+        };
+        if self.b - self.a < tolerance {
+            return None;
+        }
+        // the maximum lies on the side of the better interior point, which stays evaluated
+        if fc > fd {
+            self.b = self.d;
+            self.d = self.c;
+            self.fd = Some(fc);
+            self.c = self.b - Self::INV_PHI * (self.b - self.a);
+            self.fc = None;
+            Some(self.c)
+        } else {
+            self.a = self.c;
+            self.c = self.d;
+            self.fc = Some(fd);
+            self.d = self.a + Self::INV_PHI * (self.b - self.a);
+            self.fd = None;
+            Some(self.d)
+        }
+    }
+
+    // (pitch, efficiency) of the better evaluated interior point
+    fn best(&self) -> Option<(f32, f32)> {
+        [(self.c, self.fc), (self.d, self.fd)].into_iter()
+            .filter_map(|(pitch, efficiency)| efficiency.map(|e| (pitch, e)))
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+    }
+}
+
+// One golden-section evaluation per row, the file started over with the first
+fn append_optimizer_log(file_path: &std::path::Path, search: &GoldenSectionSearch, pitch: f32, efficiency: f32) -> Result<(), Box<dyn Error>> {
+    let file = if search.evaluations <= 1 {
+        std::fs::File::create(file_path)?
+    } else {
+        std::fs::OpenOptions::new().append(true).open(file_path)?
+    };
+    let mut wtr = Writer::from_writer(file);
+    if search.evaluations <= 1 {
+        wtr.write_record(["evaluation", "pitch", "efficiency", "bracket_low", "bracket_high"])?;
+    }
+    wtr.write_record(&[search.evaluations.to_string(), pitch.to_string(), efficiency.to_string(), search.a.to_string(), search.b.to_string()])?;
+    wtr.flush()?;
+    Ok(())
+}
+
+// Closed-loop pitch search, used when pitch_control is "pid" or the optimizer is enabled
+#[derive(SystemParam)]
+struct ThrustControl<'w> {
+    controller: Res<'w, ThrustController>,
+    state: ResMut<'w, ThrustControllerState>,
+    optimizer: Res<'w, PitchOptimizer>,
+    search: ResMut<'w, GoldenSectionSearch>,
+}
+
+// Trial bookkeeping for the pitch sweep
+#[derive(Resource, Default)]
+struct SimulationState {
+    time_elapsed: f32,
+    trial: u32,
+    pitch_index: u32,
+    adaptive_pitch: Option<f32>, // set by the ThrustController or PitchOptimizer, None while the sweep sets the pitch
+    data_row: Vec<f32>,
+    rev_per_sec_row: Vec<f32>,
+    power_row: Vec<f32>, // mean shaft power of each trial
+    drag_fraction_row: Vec<f32>, // viscous drag dissipation over shaft work of each trial
+    thrust_std_row: Vec<f32>, // per-step thrust standard deviation of each trial
+    rotor_rows: Vec<Vec<f32>>, // trial impulses of each rotor in a PropellerArray
+    rotor_pitches: Vec<f32>, // pitch of each rotor, in rotor_rows order
+    rotor_speed_rows: Vec<Vec<(f32, f32)>>, // rev/s and shaft power of each rotor per trial, in rotor_rows order
+    rotor_torque_rows: Vec<Vec<f32>>, // reaction angular impulse of each rotor per trial, signed by its spin, in rotor_rows order
+    lateral_row: Vec<Vec2>, // trial X and Z impulses per rotor
+    world_impulse_row: Vec<Vec3>, // trial impulse per rotor in world axes
+    torque_row: Vec<f32>, // trial reaction angular impulse per rotor
+    angular_v_range_row: Vec<(f32, f32)>, // (peak, min) angular_v of each trial across the rotors
+    vibration_row: Vec<f32>, // largest imbalance displacement of any hub in each trial
+    duct_row: Vec<(f32, u32)>, // duct vertical reaction impulse per rotor and duct wall hits of each trial
+    density_cv_row: Vec<f32>, // coefficient of variation of particle counts per cell of each trial
+    compression_row: Vec<f32>, // CompressionRatio mean of each trial
+    speed_stats_row: Vec<(f32, f32, f32)>, // particle speed (mean, variance, excess kurtosis) of each trial
+    slipstream_row: Vec<f32>, // mean axial slip-stream velocity at the end of each trial
+    harmonics_row: Vec<Vec<(f32, f32)>>, // FourierAnalysis harmonics of each trial
+    spectrum_peak_row: Vec<(f32, bool)>, // and its spectrum peak, off a harmonic or not
+    particle_stats_row: Vec<Vec3>, // (number density, rms speed, mean free path) at the end of each trial
+    blade_hits_row: Vec<Vec3>, // blade_hit_statistics of each trial
+    results: Vec<PitchResult>, // for the SimulationReport
+    finished: bool, // the sweep is done, the fixed steps left in its last frame must not start another trial
+}
+
+// What a pitch's coefficients and noise estimate depend on besides its trials
+struct PitchConditions {
+    density: f32,
+    inflow_speed: f32,
+    span: f32,
+    disk_area: f32,
+    blades_per_rotor: u32,
+}
+
+// A pitch averaged over its finished trials, thrust per rotor
+struct PitchSummary {
+    mean_thrust: f32,
+    mean_power: f32,
+    coefficients: PropellerCoefficients,
+    acoustics: PropellerAcoustics,
+    // output.csv rows, one per rotor in a parallel batch
+    records: Vec<LogRecord>,
+    results: Vec<PitchResult>,
+}
+
+impl SimulationState {
+    // The output.csv rows of the current pitch from the trials finished so far. The
+    // controller logs them when the pitch ends and the egui dump part way through, where
+    // the trials still to run are NaN.
+    fn pitch_summary(&self, config: &SimConfig, pitch: f32, trial_count: u32, conditions: &PitchConditions) -> PitchSummary {
+        let padded = |row: &[f32]| {
+            let mut trials = row.to_vec();
+            trials.resize(trial_count as usize, f32::NAN);
+            trials
+        };
+        let mean_impulse = self.data_row.iter().sum::<f32>() / self.data_row.len() as f32;
+
+        // per rotor, averaged over the array
+        let mean_thrust = mean_impulse / config.trial_duration;
+        let mean_rev_per_sec = self.rev_per_sec_row.iter().sum::<f32>() / self.rev_per_sec_row.len() as f32;
+        let mean_power = self.power_row.iter().sum::<f32>() / self.power_row.len() as f32;
+        let drag_power_fraction = self.drag_fraction_row.iter().sum::<f32>() / self.drag_fraction_row.len() as f32;
+        let thrust_std = self.thrust_std_row.iter().sum::<f32>() / self.thrust_std_row.len() as f32;
+        let mean_torque = self.torque_row.iter().sum::<f32>() / self.torque_row.len() as f32 / config.trial_duration;
+        // trials spanning through zero torque give no meaningful ratio
+        let thrust_torque_ratio = if mean_torque != 0.0 { mean_thrust / mean_torque } else { 0.0 };
+        let peak_angular_v = self.angular_v_range_row.iter().map(|r| r.0).sum::<f32>() / self.angular_v_range_row.len() as f32;
+        let min_angular_v = self.angular_v_range_row.iter().map(|r| r.1).sum::<f32>() / self.angular_v_range_row.len() as f32;
+        let peak_vibration = self.vibration_row.iter().sum::<f32>() / self.vibration_row.len() as f32;
+        let speed_trials = self.speed_stats_row.len() as f32;
+        let speed_mean = self.speed_stats_row.iter().map(|s| s.0).sum::<f32>() / speed_trials;
+        let speed_variance = self.speed_stats_row.iter().map(|s| s.1).sum::<f32>() / speed_trials;
+        let speed_kurtosis = self.speed_stats_row.iter().map(|s| s.2).sum::<f32>() / speed_trials;
+        let density_cv = self.density_cv_row.iter().sum::<f32>() / self.density_cv_row.len() as f32;
+        let compression_ratio = self.compression_row.iter().sum::<f32>() / self.compression_row.len() as f32;
+        let slipstream_velocity = self.slipstream_row.iter().sum::<f32>() / self.slipstream_row.len() as f32;
+        let particle_stats = self.particle_stats_row.iter().sum::<Vec3>() / self.particle_stats_row.len() as f32;
+        let blade_hits = self.blade_hits_row.iter().sum::<Vec3>() / self.blade_hits_row.len() as f32;
+        let coefficients = PropellerCoefficients::compute(mean_thrust, mean_power, mean_rev_per_sec, config.bounding_box.size().x, conditions.density, conditions.inflow_speed);
+        let acoustics = PropellerAcoustics::compute(mean_thrust, mean_rev_per_sec, conditions.span * config.simulation_scale.length_m_per_unit, conditions.disk_area,
+            conditions.density, conditions.blades_per_rotor, config.speed_of_sound);
+        let mut columns: Vec<(String, f32)> = [
+            ("mean_thrust", mean_thrust),
+            ("thrust_std", thrust_std),
+            ("J", coefficients.advance_ratio),
+            ("CT", coefficients.ct),
+            ("CP", coefficients.cp),
+            ("figure_of_merit", coefficients.figure_of_merit),
+            ("spl_db", acoustics.spl_db),
+            ("bpf_hz", acoustics.bpf_hz),
+            ("slipstream_velocity", slipstream_velocity),
+            ("reaction_torque", mean_torque),
+            ("thrust_torque_ratio", thrust_torque_ratio),
+            ("peak_angular_v", peak_angular_v),
+            ("min_angular_v", min_angular_v),
+            ("peak_vibration", peak_vibration),
+            ("speed_mean", speed_mean),
+            ("speed_variance", speed_variance),
+            ("speed_excess_kurtosis", speed_kurtosis),
+            ("density_cv", density_cv),
+            ("compression_ratio", compression_ratio),
+            ("drag_power_fraction", drag_power_fraction),
+            ("number_density", particle_stats.x),
+            ("rms_speed", particle_stats.y),
+            ("mean_free_path", particle_stats.z),
+            ("blade_hits_mean", blade_hits.x),
+            ("blade_hits_max", blade_hits.y),
+            ("blade_hits_std", blade_hits.z),
+        ].iter().map(|&(name, value)| (name.to_string(), value)).collect();
+        for h in 0..FourierAnalysis::HARMONICS {
+            let trials = self.harmonics_row.len() as f32;
+            columns.push((format!("H{}_amplitude", h + 1), self.harmonics_row.iter().map(|row| row[h].0).sum::<f32>() / trials));
+            columns.push((format!("H{}_phase", h + 1), circular_mean(self.harmonics_row.iter().map(|row| row[h].1))));
+        }
+        // 1 when any trial's spectrum peaked away from the harmonics
+        let trials = self.spectrum_peak_row.len().max(1) as f32;
+        columns.push(("spectrum_peak_hz".to_string(), self.spectrum_peak_row.iter().map(|peak| peak.0).sum::<f32>() / trials));
+        columns.push(("spectrum_peak_off_harmonic".to_string(), if self.spectrum_peak_row.iter().any(|peak| peak.1) { 1.0 } else { 0.0 }));
+        if config.duct.is_some() {
+            let trials = self.duct_row.len().max(1) as f32;
+            columns.push(("duct_thrust".to_string(), self.duct_row.iter().map(|d| d.0).sum::<f32>() / trials / config.trial_duration));
+            columns.push(("duct_wall_hits".to_string(), self.duct_row.iter().map(|d| d.1 as f32).sum::<f32>() / trials));
+        }
+        if config.cyclic_pitch.is_some() {
+            // tilted disk force, per rotor
+            let lateral = self.lateral_row.iter().sum::<Vec2>() / self.lateral_row.len() as f32 / config.trial_duration;
+            columns.push(("thrust_x".to_string(), lateral.x));
+            columns.push(("thrust_z".to_string(), lateral.y));
+        }
+        if config.tilt_rate_deg_per_second != 0.0 {
+            // the thrust vector swinging over as the disk tilts
+            let world = self.world_impulse_row.iter().sum::<Vec3>() / self.world_impulse_row.len() as f32 / config.trial_duration;
+            columns.push(("world_thrust_x".to_string(), world.x));
+            columns.push(("world_thrust_y".to_string(), world.y));
+            columns.push(("world_thrust_z".to_string(), world.z));
+        }
+        // reaction torque summed over the given rotors with their spin signs, zero when a
+        // counter-rotating pair is balanced
+        let net_torque = |rotors: &[Vec<f32>]| {
+            let trials = rotors.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            rotors.iter().flatten().sum::<f32>() / trials as f32 / config.trial_duration
+        };
+        let mut records = Vec::new();
+        let mut results = Vec::new();
+        if config.parallel_batch().is_some() {
+            // a row per pitch of the batch, each with its own rotor's thrust, coefficients and
+            // noise; the flow columns are averaged over every domain
+            for (i, ((row, speeds), &rotor_pitch)) in self.rotor_rows.iter().zip(&self.rotor_speed_rows).zip(&self.rotor_pitches).enumerate() {
+                let average = row.iter().sum::<f32>() / row.len() as f32;
+                let rotor_thrust = average / config.trial_duration;
+                let rotor_rev_per_sec = speeds.iter().map(|s| s.0).sum::<f32>() / speeds.len() as f32;
+                let rotor_power = speeds.iter().map(|s| s.1).sum::<f32>() / speeds.len() as f32;
+                let coefficients = PropellerCoefficients::compute(rotor_thrust, rotor_power, rotor_rev_per_sec, config.bounding_box.size().x, conditions.density, conditions.inflow_speed);
+                let acoustics = PropellerAcoustics::compute(rotor_thrust, rotor_rev_per_sec, conditions.span * config.simulation_scale.length_m_per_unit, conditions.disk_area,
+                    conditions.density, conditions.blades_per_rotor, config.speed_of_sound);
+                let mut columns = columns.clone();
+                for (name, value) in &mut columns {
+                    *value = match name.as_str() {
+                        "mean_thrust" => rotor_thrust,
+                        "J" => coefficients.advance_ratio,
+                        "CT" => coefficients.ct,
+                        "CP" => coefficients.cp,
+                        "figure_of_merit" => coefficients.figure_of_merit,
+                        "spl_db" => acoustics.spl_db,
+                        "bpf_hz" => acoustics.bpf_hz,
+                        _ => *value,
+                    };
+                }
+                let rotor_torque = net_torque(self.rotor_torque_rows.get(i).map(std::slice::from_ref).unwrap_or_default());
+                results.push(PitchResult::new(rotor_pitch, row.iter().map(|impulse| impulse / config.trial_duration).collect(), &coefficients, rotor_torque));
+                records.push(LogRecord { pitch: rotor_pitch, trials: padded(row), average, columns });
+            }
+        } else {
+            let trial_thrusts = self.data_row.iter().map(|impulse| impulse / config.trial_duration).collect();
+            let array_torque = net_torque(&self.rotor_torque_rows);
+            results.push(PitchResult::new(pitch, trial_thrusts, &coefficients, array_torque));
+            if self.rotor_rows.len() > 1 {
+                for (i, row) in self.rotor_rows.iter().enumerate() {
+                    let rotor_thrust = row.iter().sum::<f32>() / row.len() as f32 / config.trial_duration;
+                    columns.push((format!("rotor_{}_mean_thrust", i + 1), rotor_thrust));
+                }
+                columns.push(("net_reaction_torque".to_string(), array_torque));
+            }
+            records.push(LogRecord { pitch, trials: padded(&self.data_row), average: mean_impulse, columns });
+        }
+        PitchSummary { mean_thrust, mean_power, coefficients, acoustics, records, results }
+    }
+
+    fn current_pitch(&self, config: &SimConfig) -> f32 {
+        self.adaptive_pitch.or(config.pitch_at(self.pitch_index)).unwrap_or(config.first_pitch())
+    }
+}
+
+
+
+
+pub const COLLISION_RADIUS: f32 = 0.1;
+
+// The binary: config.toml, then the command line, then the sweep or the window
+pub fn run() {
+    let cli = Cli::parse();
+    let mut config = match SimConfig::load("config.toml") {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error loading configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
+    cli.apply(&mut config);
+    if let Err(err) = config.validate() {
+        eprintln!("Invalid configuration: {}", err);
+        std::process::exit(1);
+    }
+    if cli.dry_run {
+        match toml::to_string(&config) {
+            Ok(text) => print!("{}", text),
+            Err(err) => eprintln!("Error printing configuration: {}", err),
+        }
+        return;
+    }
+    if let Some(target) = cli.find_pitch_for_thrust {
+        let results = PropellerTestRig::run(config).expect("configuration validated above");
+        match interpolate_pitch_for_thrust(target, &results) {
+            Some(pitch) => println!("Pitch for {} N thrust: {} degrees", target, pitch),
+            None => println!("{} N is outside the measured thrust range", target),
+        }
+        return;
+    }
+    if let Some(eps) = cli.sensitivity {
+        let matrix = match ParameterSensitivityMatrix::compute(&config, eps) {
+            Ok(matrix) => matrix,
+            Err(err) => {
+                eprintln!("Sensitivity run failed: {}", err);
+                std::process::exit(1);
+            }
+        };
+        println!("Pitch {}: base thrust {} N", matrix.pitch, matrix.base_thrust);
+        for row in &matrix.rows {
+            println!("{:>14} = {}: dT/dparam {}, elasticity {}", row.parameter, row.value, row.derivative, row.elasticity);
+        }
+        if let Some(row) = matrix.dominant() {
+            println!("Thrust is most sensitive to {}", row.parameter);
+        }
+        if let Err(err) = matrix.write_csv(&config.output_dir.join("sensitivity.csv")) {
+            eprintln!("Error writing sensitivity.csv: {}", err);
+        }
+        return;
+    }
+    if let Some(tolerance) = cli.torque_balance {
+        let balances = match DualRotorTorqueBalance::run(&config, tolerance) {
+            Ok(balances) => balances,
+            Err(err) => {
+                eprintln!("Torque balance failed: {}", err);
+                std::process::exit(1);
+            }
+        };
+        for balance in &balances {
+            match balance.balance {
+                Some(root) => println!("Pitch {}: lower/upper RPM {}, net torque {} N m", balance.pitch, root.x, root.value),
+                None => println!("Pitch {}: no torque balance between lower/upper RPM {} and {}", balance.pitch, torque_balance::RATIO_BRACKET.0, torque_balance::RATIO_BRACKET.1),
+            }
+        }
+        if let Err(err) = torque_balance::write_torque_balance_csv(&config.output_dir.join("torque_balance.csv"), &balances) {
+            eprintln!("Error writing torque_balance.csv: {}", err);
+        }
+        return;
+    }
+    if config.headless {
+        for result in PropellerTestRig::sweep(config, true).expect("configuration validated above") {
+            println!("Pitch {}: mean thrust {} N, CT {}, CP {}", result.pitch, result.mean, result.ct, result.cp);
+        }
+        return;
+    }
+    build_app(config, true).run();
+}
+
+// Runs a sweep start to finish without a window or input and hands back its results,
+// for scripts, optimisation loops and the integration tests. The per-trial diagnostics are
+// only logged for the binary.
+pub struct PropellerTestRig;
+
+impl PropellerTestRig {
+    pub fn run(config: SimConfig) -> Result<Vec<PitchResult>, String> {
+        PropellerTestRig::sweep(config, false)
+    }
+
+    fn sweep(mut config: SimConfig, log: bool) -> Result<Vec<PitchResult>, String> {
+        config.headless = true;
+        config.validate()?;
+        let mut app = build_app(config, log);
+        app.finish();
+        app.cleanup();
+        let mut exit_reader = bevy::ecs::event::ManualEventReader::<AppExit>::default();
+        loop {
+            app.update();
+            if exit_reader.read(app.world.resource::<Events<AppExit>>()).next().is_some() {
+                break;
+            }
+        }
+        Ok(app.world.resource::<SimulationState>().results.clone())
+    }
+}
+
+// move_particles and wall_collisions over a spawned default box, for benches/particle_update.rs
+#[doc(hidden)]
+pub struct ParticleUpdateBench {
+    app: App,
+    step: Schedule,
+}
+
+impl ParticleUpdateBench {
+    pub fn new(particle_count: usize) -> Self {
+        let mut app = build_app(SimConfig { headless: true, particle_count, seed: Some(1), ..SimConfig::default() }, false);
+        app.finish();
+        app.cleanup();
+        // spawns the particles
+        app.update();
+        let mut step = Schedule::default();
+        step.add_systems((move_particles, wall_collisions).chain());
+        ParticleUpdateBench { app, step }
+    }
+
+    // one physics step, iterating in parallel above parallel_threshold particles
+    pub fn step(&mut self, parallel_threshold: usize) {
+        self.app.world.insert_resource(ParallelThreshold(parallel_threshold));
+        self.step.run(&mut self.app.world);
+    }
+}
+
+// log sets up the log output in headless mode, a window always has it
+fn build_app(config: SimConfig, log: bool) -> App {
+    let geometry = config.geometry;
+    let bounds = config.bounding_box;
+    let particle_count = config.particle_count;
+    let elastic_modulus = config.material.as_ref().map_or(config.elastic_modulus, |material| material.elastic_modulus);
+
+    let mut app = App::new();
+    if config.headless {
+        // MinimalPlugins already brings the time plugin and a schedule runner
+        app.add_plugins(MinimalPlugins);
+        if log {
+            app.add_plugins(bevy::log::LogPlugin::default());
+        }
+    } else {
+        app.add_plugins(DefaultPlugins)
+            .init_resource::<DebugLines>()
+            .add_systems(Update, (draw_boundary_cube, draw_debug_lines, handle_time_scale_input.before(apply_time_scale)))
+            .init_resource::<ShowVelocityVectors>()
+            .init_resource::<VelocityVectorSettings>()
+            .add_systems(Update, (handle_pause_input.before(PhysicsSet), orbit_camera_system, update_particle_colors, toggle_velocity_vectors, draw_velocity_vectors))
+            .add_systems(Update, (handle_recording_input, visualize_slipstream, visualize_pressure_field))
+            .init_resource::<ShowPropellerDisk>()
+            .add_systems(Startup, spawn_propeller_disk)
+            .add_systems(Update, (toggle_propeller_disk, update_propeller_disk_visibility, draw_disk_zones, draw_blade_heatmap, draw_polar_density))
+            .init_resource::<ThrustGraph>()
+            .add_systems(Update, (record_thrust_graph.before(controller), draw_thrust_graph))
+            .add_systems(Update, (stamp_collision_times, color_particles_by_recency.after(stamp_collision_times)))
+            .init_resource::<ManualMeasurement>()
+            .add_systems(Update, (toggle_manual_control.before(manual_pitch_control), manual_pitch_control))
+            .init_resource::<CrossSectionView>()
+            .add_systems(Update, (handle_cross_section_input.before(render_cross_section), render_cross_section))
+            .add_systems(FixedUpdate, (record_frame.after(PhysicsSet), replay_frame));
+
+        #[cfg(feature = "ui")]
+        app.add_plugins(EguiPlugin)
+            .init_resource::<ThrustDisplay>()
+            .insert_resource(AltitudeModel { altitude_m: config.altitude_m.unwrap_or(0.0) })
+            .init_resource::<TemperatureDisplay>()
+            .add_systems(Update, (egui_ui_system, egui_temperature_plot, egui_time_scale_overlay, egui_pitch_for_thrust))
+            .add_event::<DumpCsvRequest>()
+            .add_systems(Update, dump_partial_pitch.after(egui_ui_system));
+    }
+    info!("Blade aspect ratio: {}", geometry.aspect_ratio());
+
+    if let Some(cyclic) = config.cyclic_pitch {
+        app.insert_resource(cyclic);
+    }
+    if let Some(wake) = config.wake_vortex {
+        app.insert_resource(wake)
+            .add_systems(FixedUpdate, apply_wake_vortex.after(run_propeller_substeps).in_set(PhysicsSet));
+        if !config.headless {
+            app.add_systems(Update, draw_wake_vortex);
+        }
+    }
+    if let Some(downwash) = config.downwash {
+        app.insert_resource(downwash)
+            .add_systems(FixedUpdate, apply_downwash.after(run_propeller_substeps).in_set(PhysicsSet));
+    }
+    if config.benchmark {
+        app.add_plugins(BenchmarkPlugin { write_csv: config.headless });
+    }
+    if let Some(duct) = config.duct {
+        app.insert_resource(duct)
+            .init_resource::<DuctThrust>()
+            .add_systems(FixedUpdate, duct_collisions.after(wall_collisions).before(build_octree).in_set(PhysicsSet));
+        if !config.headless {
+            app.add_systems(Startup, spawn_duct_mesh.after(setup))
+                .add_systems(Update, follow_hub_duct);
+        }
+    }
+    if let Some(material) = config.material.clone() {
+        app.insert_resource(material);
+    }
+    if let Some(heat_capacity) = config.heat_capacity {
+        app.insert_resource(HeatCapacity(heat_capacity));
+    }
+    if let Some(sutherland) = config.temperature_dependent_viscosity {
+        app.insert_resource(sutherland)
+            .add_systems(FixedUpdate, update_viscosity.before(PhysicsSet));
+    }
+
+    app
+        .init_resource::<SimulationState>()
+        .init_resource::<ThrustControllerState>()
+        .insert_resource(config.thrust_controller)
+        .insert_resource(config.pitch_optimizer)
+        .insert_resource(GoldenSectionSearch::new(&config.pitch_optimizer))
+        .insert_resource(CsvOutputConfig { file_path: config.output_path.clone(), delimiter: config.csv_delimiter, ..default() })
+        .insert_resource(config.efficiency_plot.clone())
+        .init_resource::<PropellerCoefficients>()
+        .init_resource::<PropellerAcoustics>()
+        .insert_resource(config.fluid())
+        .insert_resource(bounds)
+        .insert_resource(Viscosity(config.viscosity))
+        .insert_resource(FluidTemperature(config.fluid().temperature_k))
+        .insert_resource(ViscousDragEnabled(config.viscous_drag))
+        .init_resource::<ViscousDissipation>()
+        .init_resource::<ReynoldsNumber>()
+        .insert_resource(Gravity { mode: config.gravity_mode, scale: config.gravity_scale, ..default() })
+        .insert_resource(DataLoggerConfig { output_dir: config.output_dir.clone(), format: config.output_format })
+        .add_plugins(DataLoggerPlugin)
+        .insert_resource(TrialCount(config.trial_count))
+        .insert_resource(RandomSeed(config.seed))
+        .insert_resource(SimRng::new(RandomSeed(config.seed)))
+        .insert_resource(Restitution { particle_particle: config.restitution, blade_particle: config.blade_restitution })
+        .init_resource::<HeatGenerated>()
+        .insert_resource(config.wind_profile)
+        .insert_resource(SimulationPhase::Warmup { steps: 0 })
+        // a ramp longer than the warmup ends with it
+        .insert_resource(PropellerStartup { startup_duration: config.startup_duration.min(config.warmup_duration), timestep: config.fixed_timestep })
+        .insert_resource(config.rotor_array())
+        .insert_resource(config.ground_plane)
+        .insert_resource(config.physics_mode)
+        .insert_resource(config.solver_mode)
+        .insert_resource(BoundaryConditions(config.boundary_conditions))
+        .init_resource::<ParticleCount>()
+        .init_resource::<OutflowFlux>()
+        .init_resource::<WindTunnelFlux>()
+        .init_resource::<ParallelThreshold>()
+        .init_resource::<ParticlePool>()
+        .insert_resource(config.rotor_governor.unwrap_or(RotorGovernor::ConstantPower(config.power_input)))
+        .init_resource::<GovernorState>()
+        .insert_resource(Time::<Fixed>::from_seconds(config.fixed_timestep as f64))
+        .insert_resource(TimeScale::new(config.time_scale))
+        .add_systems(First, apply_time_scale)
+        .insert_resource(config.particle_color_mode)
+        .insert_resource(MaxParticleSpeed(config.max_particle_speed()))
+        .insert_resource(MountCompliance(config.imbalance.compliance))
+        .insert_resource(MinParticleSpeed(config.min_particle_speed))
+        .init_resource::<OverspeedDiagnostic>()
+        .init_resource::<ParticleStatistics>()
+        .init_resource::<FourierAnalysis>()
+        .insert_resource(SpeedOfSound(config.speed_of_sound))
+        .insert_resource(config.simulation_scale)
+        .insert_resource(config.vtk_export.clone())
+        .insert_resource(config.density_regulator.clone())
+        .insert_resource(EnergyDiagnostic { tolerance: config.energy_tolerance, bem_comparison: config.energy_bem_comparison, ..default() })
+        .insert_resource(BladeHeatmap::new(config.heatmap_bins))
+        .insert_resource(PolarDensityField::new(config.polar_ring_bins, config.polar_angle_bins))
+        .init_resource::<CompressionRatio>()
+        .insert_resource(config)
+        .insert_resource(SpatialGrid::new(bounds.volume(), particle_count, COLLISION_RADIUS))
+        .insert_resource(Octree::new(Vec3::ZERO, bounds.half_extent.max_element()))
+        .insert_resource(PressureField::new(1.0))
+        .init_resource::<VelocityHistogram>()
+        // the lower half of the box, below the rotor
+        .insert_resource(SlipStreamField::new(-bounds.half_extent, bounds.size() * Vec3::new(1.0, 0.5, 1.0), UVec3::new(10, 5, 10)))
+        .insert_resource(BladeLoadDistribution::new(geometry.span, 8))
+        .insert_resource(PropellerMetrics::new(&geometry))
+        .insert_resource(geometry)
+        .init_resource::<NacaProfile>()
+        .init_resource::<PropellerThrustRipple>()
+        .init_resource::<ThrustHistory>()
+        .init_resource::<PropellerStartupTransient>()
+        .init_resource::<ThrustMomentCoupling>()
+        .insert_resource(MeshDeformationSimulator::tapered(&geometry, 8, elastic_modulus))
+        .add_systems(Startup, (setup, spawn_particles))
+        .init_resource::<SubstepCount>()
+        .init_resource::<SubstepTime>()
+        .init_resource::<SimulationPaused>()
+        .init_resource::<SingleStep>()
+        .init_resource::<Recording>()
+        .configure_sets(Update, PhysicsSet.run_if(physics_running))
+        .configure_sets(FixedUpdate, PhysicsSet.run_if(physics_running))
+        // physics steps at fixed_timestep however fast frames render, and everything that feeds the
+        // results runs in the same fixed steps, so a seeded run writes the same files at any frame rate
+        .add_systems(FixedUpdate, (emit_particles.before(move_particles), inject_wind_tunnel.before(move_particles), update_particle_mass, rebuild_spatial_grid.before(compare_particles), move_particles, wall_collisions, compare_particles, build_octree.after(wall_collisions).after(compare_particles).before(run_propeller_substeps), run_propeller_substeps, update_propeller_moi.before(run_propeller_substeps), update_compressibility.before(run_propeller_substeps), animate_tilt.before(run_propeller_substeps), record_thrust_ripple.after(run_propeller_substeps), record_startup_transient.after(run_propeller_substeps), update_slipstream.after(run_propeller_substeps), compute_pressure_field.after(run_propeller_substeps), update_velocity_histogram.after(run_propeller_substeps), check_energy_conservation.after(run_propeller_substeps), vtk_export_system.after(run_propeller_substeps), regulate_density.after(wall_collisions).before(rebuild_spatial_grid).before(build_octree)).in_set(PhysicsSet))
+        .init_resource::<ManualControl>()
+        .add_systems(FixedUpdate, (transition_phase.before(controller), controller).after(PhysicsSet).before(end_single_step).run_if(physics_running).run_if(resource_equals(ManualControl(false))))
+        .add_event::<BladeParticleCollision>()
+        .add_event::<ParticleParticleCollision>()
+        .init_resource::<ParticleParticleCollisionStats>()
+        .add_systems(FixedUpdate, (log_collisions, compute_particle_statistics, compute_polar_density, compute_reynolds_number, track_particle_collisions).after(PhysicsSet).before(transition_phase))
+        .add_systems(PropellerSubstep, (update_rectangle_rotation, (
+            blade_collisions.run_if(resource_equals(PhysicsMode::MolecularDynamics)),
+            blade_element_forces.run_if(resource_equals(PhysicsMode::BladeElement)),
+        )).chain())
+        // a frame may run no fixed step, so the request stays up until one has
+        .add_systems(FixedUpdate, end_single_step.after(PhysicsSet));
+    app
+}
+
+// Mesh and material for a rendered body, None when running headless
+type RenderHandles = Option<(Handle<Mesh>, Handle<StandardMaterial>)>;
+
+// Spawns components with a PbrBundle, or with only a transform when headless
+fn spawn_body(commands: &mut Commands, render: &RenderHandles, transform: Transform, components: impl Bundle) -> Entity {
+    match render {
+        Some((mesh, material)) => commands.spawn((
+            PbrBundle { mesh: mesh.clone(), material: material.clone(), transform, ..default() },
+            components,
+        )).id(),
+        None => commands.spawn((TransformBundle::from_transform(transform), components)).id(),
+    }
+}
+
+// Space toggles pause, period steps a single frame while paused
+fn handle_pause_input(keys: Res<Input<KeyCode>>, mut paused: ResMut<SimulationPaused>, mut step: ResMut<SingleStep>) {
+    if keys.just_pressed(KeyCode::Space) {
+        paused.0 = !paused.0;
+    }
+    if paused.0 && keys.just_pressed(KeyCode::Period) {
+        step.0 = true;
+    }
+}
+
+fn handle_time_scale_input(keys: Res<Input<KeyCode>>, mut scale: ResMut<TimeScale>) {
+    let mut change = 0.0;
+    if keys.just_pressed(KeyCode::Equals) || keys.just_pressed(KeyCode::NumpadAdd) {
+        change += TimeScale::STEP;
+    }
+    if keys.just_pressed(KeyCode::Minus) || keys.just_pressed(KeyCode::NumpadSubtract) {
+        change -= TimeScale::STEP;
+    }
+    if change != 0.0 {
+        // steps of 0.1 from 0.1 up, so slowing down from 0.1 lands on the 0.01 floor
+        *scale = TimeScale::new(((scale.0 + change) / TimeScale::STEP).round() * TimeScale::STEP);
+    }
+}
+
+fn apply_time_scale(scale: Res<TimeScale>, mut virtual_time: ResMut<Time<Virtual>>) {
+    if virtual_time.relative_speed() != scale.0 {
+        virtual_time.set_relative_speed(scale.0);
+    }
+}
+
+fn draw_debug_lines(mut gizmos: Gizmos, mut lines: ResMut<DebugLines>, real: Res<Time<Real>>, scale: Res<TimeScale>) {
+    // longer in slow motion so a strike is still on screen while the blade moves past it
+    let lifetime = DEBUG_LINE_LIFETIME / scale.0;
+    let dt = real.delta_seconds();
+    lines.0.retain_mut(|(start, end, color, age)| {
+        gizmos.line(*start, *end, *color);
+        *age += dt;
+        *age < lifetime
+    });
+}
+
+fn physics_running(paused: Res<SimulationPaused>, step: Res<SingleStep>, recording: Res<Recording>, state: Res<SimulationState>) -> bool {
+    (!paused.0 || step.0) && !recording.playing && !state.finished
+}
+
+fn end_single_step(mut step: ResMut<SingleStep>) {
+    step.0 = false;
+}
+
+fn handle_recording_input(keys: Res<Input<KeyCode>>, mut recording: ResMut<Recording>) {
+    if keys.just_pressed(KeyCode::R) && !recording.playing {
+        recording.recording = !recording.recording;
+        if recording.recording {
+            recording.frames.clear();
+        } else if let Err(err) = export_recording(RECORDING_PATH, &recording.frames) {
+            eprintln!("Error writing recording: {}", err);
+        }
+    }
+    if keys.just_pressed(KeyCode::P) && !recording.recording {
+        if recording.playing {
+            recording.playing = false;
+            return;
+        }
+        if recording.frames.is_empty() {
+            match import_recording(RECORDING_PATH) {
+                Ok(frames) => recording.frames = frames,
+                Err(err) => {
+                    eprintln!("Error reading recording: {}", err);
+                    return;
+                }
+            }
+        }
+        recording.playing = true;
+        recording.cursor = 0;
+    }
+}
+
+fn record_frame(
+    mut recording: ResMut<Recording>,
+    particle_query: Query<(Entity, &Transform, &Particle)>,
+    hub_query: Query<(Entity, &PropellerHub)>,
+    blade_query: Query<(Entity, &Transform), With<PropellerBlade>>,
+) {
+    if !recording.recording {
+        return;
+    }
+    let frame = FrameSnapshot {
+        particles: particle_query.iter().map(|(entity, transform, particle)| (entity, *transform, particle.clone())).collect(),
+        hubs: hub_query.iter().map(|(entity, hub)| (entity, hub.clone())).collect(),
+        blades: blade_query.iter().map(|(entity, transform)| (entity, *transform)).collect(),
+    };
+    recording.frames.push(frame);
+}
+
+fn replay_frame(
+    mut recording: ResMut<Recording>,
+    mut particle_query: Query<(&mut Transform, &mut Particle), Without<PropellerBlade>>,
+    mut hub_query: Query<&mut PropellerHub>,
+    mut blade_query: Query<&mut Transform, With<PropellerBlade>>,
+) {
+    if !recording.playing {
+        return;
+    }
+    let Some(frame) = recording.frames.get(recording.cursor) else {
+        recording.playing = false;
+        return;
+    };
+    for (entity, transform, particle) in &frame.particles {
+        if let Ok((mut current_transform, mut current_particle)) = particle_query.get_mut(*entity) {
+            *current_transform = *transform;
+            current_particle.velocity = particle.velocity;
+        }
+    }
+    for (entity, hub) in &frame.hubs {
+        if let Ok(mut current) = hub_query.get_mut(*entity) {
+            *current = hub.clone();
+        }
+    }
+    for (entity, transform) in &frame.blades {
+        if let Ok(mut current) = blade_query.get_mut(*entity) {
+            *current = *transform;
+        }
+    }
+    recording.cursor += 1;
+}
+
+fn export_recording(file_path: &str, frames: &[FrameSnapshot]) -> Result<(), Box<dyn Error>> {
+    let file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+    bincode::serialize_into(file, frames)?;
+    Ok(())
+}
+
+fn import_recording(file_path: &str) -> Result<Vec<FrameSnapshot>, Box<dyn Error>> {
+    let file = std::io::BufReader::new(std::fs::File::open(file_path)?);
+    Ok(bincode::deserialize_from(file)?)
+}
+
+// Right mouse drag orbits, scroll wheel zooms
+fn orbit_camera_system(
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    buttons: Res<Input<MouseButton>>,
+    mut query: Query<(&mut OrbitCamera, &mut Transform)>,
+) {
+    let mut delta = Vec2::ZERO;
+    for event in motion.read() {
+        delta += event.delta;
+    }
+    if !buttons.pressed(MouseButton::Right) {
+        delta = Vec2::ZERO;
+    }
+
+    let mut scroll = 0.0;
+    for event in wheel.read() {
+        scroll += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y * 0.01,
+        };
+    }
+
+    for (mut orbit, mut transform) in query.iter_mut() {
+        orbit.yaw -= delta.x * 0.3;
+        // stop short of the poles to avoid gimbal lock
+        orbit.pitch = (orbit.pitch + delta.y * 0.3).clamp(-89.0, 89.0);
+        orbit.radius = (orbit.radius * (1.0 - scroll * 0.1)).max(1.0);
+        *transform = orbit.transform();
+    }
+}
+
+// Top-left readout of the running trial
+#[cfg(feature = "ui")]
+fn egui_ui_system(
+    mut contexts: EguiContexts,
+    hub_query: Query<(Entity, &PropellerHub)>,
+    blade_query: Query<&PropellerBlade>,
+    state: Res<SimulationState>,
+    mut phase: ResMut<SimulationPhase>,
+    mut display: ResMut<ThrustDisplay>,
+    config: Res<SimConfig>,
+    coefficients: Res<PropellerCoefficients>,
+    mut dump: EventWriter<DumpCsvRequest>,
+    trial_count: Res<TrialCount>,
+    mut altitude: ResMut<AltitudeModel>,
+    mut fluid: ResMut<FluidDensity>,
+    particle_stats: Res<ParticleStatistics>,
+    manual: Res<ManualControl>,
+    measurement: Res<ManualMeasurement>,
+    compression: Res<CompressionRatio>,
+) {
+    // the overlay follows the first rotor of an array
+    let Some((hub_entity, prop)) = hub_query.iter().next() else {
+        return;
+    };
+    let pitch = hub_pitch(hub_entity, blade_query.iter()).unwrap_or(config.first_pitch());
+
+    display.samples.push_back(prop.total_vertical_impulse);
+    while display.samples.len() > display.window {
+        display.samples.pop_front();
+    }
+    let rolling_impulse = display.samples.iter().sum::<f32>() / display.samples.len() as f32;
+
+    let mut skip_trial = false;
+    let mut dump_csv = false;
+    egui::Window::new("Thrust")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(10.0, 10.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Pitch: {:.1} deg", pitch));
+            ui.label(format!("Angular velocity: {:.1} deg/s", prop.angular_v));
+            ui.label(format!("Trial time: {:.2} s", state.time_elapsed));
+            if manual.0 {
+                // Up/Down change pitch, Return records, M hands back to the sweep
+                let rate = if measurement.elapsed > 0.0 { prop.total_vertical_impulse / measurement.elapsed } else { 0.0 };
+                ui.label(format!("Manual pitch, thrust rate: {:.3} N", rate));
+            } else {
+                ui.label(format!("Trial: {} / {}", state.trial + 1, trial_count.0));
+            }
+            ui.label(format!("Vertical impulse ({} frame avg): {:.3}", display.window, rolling_impulse));
+            ui.separator();
+            ui.label(format!("J: {:.3}  CT: {:.4}  CP: {:.4}", coefficients.advance_ratio, coefficients.ct, coefficients.cp));
+            ui.label(format!("Figure of merit: {:.3}", coefficients.figure_of_merit));
+            // CT and CP should hold steady as the density drops
+            if ui.add(egui::Slider::new(&mut altitude.altitude_m, 0.0..=10000.0).text("Altitude (m)")).changed() {
+                fluid.density_kg_per_m3 = air_density_at_altitude(altitude.altitude_m);
+            }
+            ui.label(format!("Density: {:.3} kg/m^3", fluid.density_kg_per_m3));
+            ui.separator();
+            ui.label(format!("Number density: {:.3} /m^3  RMS speed: {:.3}", particle_stats.number_density, particle_stats.rms_speed));
+            ui.label(format!("Mean free path: {:.3} (box {:.1})", particle_stats.mean_free_path, config.bounding_box.size().max_element()));
+            ui.label(format!("Compression ahead / behind blade: {:.2}", compression.ratio));
+            skip_trial = ui.button("Skip Trial").clicked();
+            dump_csv = ui.button("Dump CSV Now").clicked();
+        });
+
+    // the controller ends the trial on the next fixed step
+    if skip_trial && matches!(*phase, SimulationPhase::Collecting { .. }) {
+        *phase = SimulationPhase::Resetting;
+    }
+    if dump_csv {
+        dump.send(DumpCsvRequest);
+    }
+}
+
+// Sent by the "Dump CSV Now" button
+#[cfg(feature = "ui")]
+#[derive(Event)]
+struct DumpCsvRequest;
+
+// Logs the current pitch's finished trials as an output.csv row, the same columns the
+// controller writes when the pitch ends. The running trial is left out, its impulse is partial.
+#[cfg(feature = "ui")]
+fn dump_partial_pitch(mut requests: EventReader<DumpCsvRequest>, hub_query: Query<Entity, With<PropellerHub>>, blade_query: Query<&PropellerBlade>, state: Res<SimulationState>,
+config: Res<SimConfig>, trial_count: Res<TrialCount>, fluid: Res<FluidDensity>, wind: Res<WindProfile>, geometry: Res<PropellerGeometry>, metrics: Res<PropellerMetrics>, logger: Res<DataLogger>) {
+    if requests.read().count() == 0 {
+        return;
+    }
+    let Ok(hub_entity) = hub_query.get_single() else {
+        return;
+    };
+    let pitch = hub_pitch(hub_entity, blade_query.iter()).unwrap_or(config.first_pitch());
+    if state.data_row.is_empty() {
+        info!("No finished trial at this pitch yet, nothing to dump");
+        return;
+    }
+    let conditions = PitchConditions {
+        density: fluid.density_kg_per_m3,
+        inflow_speed: wind.inflow_speed(),
+        span: geometry.span,
+        disk_area: metrics.disk_area,
+        blades_per_rotor: (blade_query.iter().count() as f32 / state.rotor_rows.len().max(1) as f32).round() as u32,
+    };
+    for record in state.pitch_summary(&config, pitch, trial_count.0, &conditions).records {
+        logger.send(record);
+    }
+}
+
+// Bottom-left chart of the fluid temperature, rising as viscous and collision heat builds up
+#[cfg(feature = "ui")]
+fn egui_temperature_plot(mut contexts: EguiContexts, mut display: ResMut<TemperatureDisplay>, temperature: Res<FluidTemperature>, time: Res<Time>) {
+    display.samples.push_back((time.elapsed_seconds(), temperature.0));
+    while display.samples.len() > display.max_samples {
+        display.samples.pop_front();
+    }
+    let (Some(&(start, _)), Some(&(end, _))) = (display.samples.front(), display.samples.back()) else {
+        return;
+    };
+    let low = display.samples.iter().map(|s| s.1).fold(f32::INFINITY, f32::min);
+    let high = display.samples.iter().map(|s| s.1).fold(f32::NEG_INFINITY, f32::max);
+    egui::Window::new("Fluid temperature")
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("{:.4} K (range {:.4} to {:.4})", temperature.0, low, high));
+            let (response, painter) = ui.allocate_painter(egui::vec2(240.0, 100.0), egui::Sense::hover());
+            let rect = response.rect;
+            let span = (end - start).max(f32::EPSILON);
+            let range = (high - low).max(1.0e-6);
+            let points: Vec<egui::Pos2> = display.samples.iter()
+                .map(|&(t, k)| egui::pos2(rect.left() + (t - start) / span * rect.width(), rect.bottom() - (k - low) / range * rect.height()))
+                .collect();
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, egui::Color32::LIGHT_RED)));
+        });
+}
+
+#[cfg(feature = "ui")]
+fn egui_time_scale_overlay(mut contexts: EguiContexts, scale: Res<TimeScale>) {
+    egui::Window::new("Time scale")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Time scale {:.2}x (+/- to change)", scale.0));
+        });
+}
+
+// Pitch for a requested thrust, interpolated from the pitches finished so far
+#[cfg(feature = "ui")]
+fn egui_pitch_for_thrust(mut contexts: EguiContexts, state: Res<SimulationState>, mut target_thrust: Local<f32>) {
+    egui::Window::new("Target Thrust")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 10.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Target thrust (N)");
+                ui.add(egui::DragValue::new(&mut *target_thrust).speed(0.1));
+            });
+            match interpolate_pitch_for_thrust(*target_thrust, &state.results) {
+                Some(pitch) => ui.label(format!("Required pitch: {:.2} deg", pitch)),
+                None => ui.label(format!("Outside the {} pitches measured so far", state.results.len())),
+            };
+        });
+}
+
+// Setup camera and lighting
+fn setup(mut commands: Commands, meshes: Option<ResMut<Assets<Mesh>>>, materials: Option<ResMut<Assets<StandardMaterial>>>, config: Res<SimConfig>, geometry: Res<PropellerGeometry>,
+array: Res<PropellerArray>, material: Option<Res<PropellerMaterial>>) {
+
+    let (blade_render, hub_render): (RenderHandles, RenderHandles) = match (meshes, materials) {
+        (Some(mut meshes), Some(mut materials)) => {
+            let orbit = OrbitCamera::from_position(Vec3::new(-10.0, 12.0, 15.0), Vec3::ZERO);
+            commands.spawn((
+                Camera3dBundle {
+                    transform: orbit.transform(),
+                    ..default()
+                },
+                orbit,
+            ));
+
+            commands.spawn(PointLightBundle {
+                transform: Transform::from_xyz(0.0, 10.0, 0.0),
+                ..default()
+            });
+
+            let hub_mesh = Mesh::try_from(shape::Icosphere { radius: config.hub_geometry.radius, subdivisions: 3 })
+                .expect("Failed to create hub mesh");
+            (
+                Some((
+                    meshes.add(geometry.planform_mesh()),
+                    materials.add(StandardMaterial {
+                        base_color: Color::rgb(0.0, 0.0, 1.0), // Blue color
+                        ..default()
+                    }),
+                )),
+                Some((meshes.add(hub_mesh), materials.add(StandardMaterial { base_color: Color::GRAY, ..default() }))),
+            )
+        }
+        _ => (None, None),
+    };
+
+    let blade_mass = match material {
+        Some(material) => {
+            let mass = material.blade_mass(&geometry);
+            info!("Blade mass in {}: {} kg", material.name, mass);
+            mass
+        }
+        None => config.propeller_mass,
+    };
+    for (index, hub_position) in array.hub_positions().into_iter().enumerate() {
+        let hub = spawn_body(
+            &mut commands,
+            &hub_render,
+            Transform::from_translation(hub_position),
+            PropellerHub { rotation_z: 0.0, old_rotation_z: 0.0, angular_v: config.start_prop_velocity, mass: blade_mass, moi: 0.0, total_vertical_impulse: 0.0, total_x_impulse: 0.0, total_z_impulse: 0.0,
+                total_reaction_torque: 0.0, peak_angular_v: config.start_prop_velocity, min_angular_v: config.start_prop_velocity,
+                rotation_direction: array.rotation_direction(index), hub_geometry: config.hub_geometry, tip_mach: 0.0, compressibility_factor: 1.0, total_world_impulse: Vec3::ZERO,
+                imbalance_mass: config.imbalance.imbalance_mass, imbalance_radius: config.imbalance.imbalance_radius, vibration_offset: Vec3::ZERO, peak_vibration: 0.0 },
+        );
+        commands.entity(hub).insert(HubGovernor { rpm_ratio: array.rpm_ratio(index), ..default() });
+        if config.tilt_rate_deg_per_second != 0.0 {
+            commands.entity(hub).insert(TiltAngle { angle_deg: 0.0, axis: config.tilt_axis });
+        }
+        if let Some(wake) = config.wake_vortex {
+            commands.spawn(WakeVortex { hub, strength: 0.0, radius: geometry.span, helix_pitch: wake.helix_pitch });
+        }
+
+        // two blades, 180 degrees apart
+        let pitch = config.rotor_pitch(index);
+        for azimuth in [0.0, 180.0] {
+            let (translation, rotation) = blade_transform(azimuth, pitch, geometry.span);
+            spawn_body(
+                &mut commands,
+                &blade_render,
+                Transform { translation: hub_position + translation, rotation, ..default() },
+                PropellerBlade { hub, pitch, azimuth, offset: Vec3::ZERO, length: geometry.span,
+                    elements: blade_elements(&geometry, config.blade_elements, pitch) },
+            );
+        }
+    }
+}
+
+fn spawn_propeller_disk(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>,
+geometry: Res<PropellerGeometry>, array: Res<PropellerArray>) {
+    let mesh = meshes.add(Mesh::from(shape::Circle { radius: geometry.span, vertices: 64 }));
+    let material = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.0, 0.5, 1.0, 0.15),
+        alpha_mode: AlphaMode::Blend,
+        double_sided: true,
+        cull_mode: None,
+        unlit: true,
+        ..default()
+    });
+    for hub_position in array.hub_positions() {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                // the circle faces +Z, lay it in the XZ plane of the rotor
+                transform: Transform::from_translation(hub_position).with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)),
+                ..default()
+            },
+            PropellerDiskMarker,
+        ));
+    }
+}
+
+// Particle speeds sampled every sample_every physics steps over a trial. Speeds past
+// max_speed land in the last bin. The raw moments give exact statistics.
+#[derive(Resource, Clone)]
+struct VelocityHistogram {
+    bins: Vec<u32>,
+    min_speed: f32,
+    max_speed: f32,
+    bin_count: usize,
+    sample_every: u32,
+    steps: u32,
+    // count and sums of |v|, |v|^2, |v|^3, |v|^4
+    moments: [f64; 5],
+}
+
+impl Default for VelocityHistogram {
+    fn default() -> Self {
+        VelocityHistogram { bins: vec![0; 20], min_speed: 0.0, max_speed: 10.0, bin_count: 20, sample_every: 10, steps: 0, moments: [0.0; 5] }
+    }
+}
+
+impl VelocityHistogram {
+    fn add(&mut self, speed: f32) {
+        let width = (self.max_speed - self.min_speed) / self.bin_count as f32;
+        let bin = ((speed - self.min_speed) / width).floor().clamp(0.0, (self.bin_count - 1) as f32) as usize;
+        self.bins[bin] += 1;
+        let v = speed as f64;
+        for (power, moment) in self.moments.iter_mut().enumerate() {
+            *moment += v.powi(power as i32);
+        }
+    }
+
+    // (bin centre, probability density) per bin
+    fn density(&self) -> Vec<(f32, f32)> {
+        let width = (self.max_speed - self.min_speed) / self.bin_count as f32;
+        let total = self.bins.iter().sum::<u32>().max(1) as f32;
+        self.bins.iter().enumerate().map(|(i, &count)| (self.min_speed + (i as f32 + 0.5) * width, count as f32 / (total * width))).collect()
+    }
+
+    // (mean, variance, excess kurtosis) of the sampled speeds
+    fn statistics(&self) -> (f32, f32, f32) {
+        let n = self.moments[0];
+        if n == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let [_, s1, s2, s3, s4] = self.moments;
+        let mean = s1 / n;
+        let variance = s2 / n - mean * mean;
+        let m4 = s4 / n - 4.0 * mean * s3 / n + 6.0 * mean * mean * s2 / n - 3.0 * mean.powi(4);
+        let kurtosis = if variance > 0.0 { m4 / (variance * variance) - 3.0 } else { 0.0 };
+        (mean as f32, variance as f32, kurtosis as f32)
+    }
+
+    fn clear(&mut self) {
+        self.bins = vec![0; self.bin_count];
+        self.moments = [0.0; 5];
+    }
+}
+
+// Kinetic energy budget of the last physics step. A step where the particle and rotor energy
+// change differs from the work put in by more than tolerance times the total energy is
+// reported. collision_loss is what inelastic particle contacts dissipated since the last check.
+#[derive(Resource, Default)]
+struct EnergyDiagnostic {
+    particle_ke: f32,
+    rotor_ke: f32,
+    work_input: f32,
+    collision_loss: f32,
+    tolerance: f32,
+    bem_comparison: bool,
+    // from the shaft power, and from a blade element evaluation at the same state when compared
+    power_coefficient: f32,
+    bem_power_coefficient: Option<f32>,
+    particle_count: usize,
+    initialized: bool,
+    last_warning: f32,
+    // summed budget misses of the running trial, logged with the solver when the next begins
+    trial_drift: f32,
+    // J dissipated by collisions and viscous drag since the start
+    heat: f32,
+}
+
+// Evens out particle crowding every regulation_interval seconds by teleporting the surplus of
+// crowded cells into sparse ones, velocities untouched. target_density is particles per m^3,
+// 0 holds the current mean. The non-uniformity is measured whether or not it is enabled.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct DensityRegulator {
+    enabled: bool,
+    target_density: f32,
+    regulation_interval: f32,
+    // coefficient of variation of the cell counts, summed over the trial's passes
+    #[serde(skip)]
+    cv_sum: f32,
+    #[serde(skip)]
+    cv_samples: u32,
+}
+
+impl Default for DensityRegulator {
+    fn default() -> Self {
+        DensityRegulator { enabled: false, target_density: 0.0, regulation_interval: 0.5, cv_sum: 0.0, cv_samples: 0 }
+    }
+}
+
+impl DensityRegulator {
+    fn mean_cv(&self) -> f32 {
+        if self.cv_samples == 0 { 0.0 } else { self.cv_sum / self.cv_samples as f32 }
+    }
+
+    fn reset_cv(&mut self) {
+        self.cv_sum = 0.0;
+        self.cv_samples = 0;
+    }
+}
+
+// Per-trial diagnostics the controller reports and resets
+#[derive(SystemParam)]
+struct TrialDiagnostics<'w> {
+    blade_load: ResMut<'w, BladeLoadDistribution>,
+    deformation: Res<'w, MeshDeformationSimulator>,
+    ripple: ResMut<'w, PropellerThrustRipple>,
+    transient: ResMut<'w, PropellerStartupTransient>,
+    coupling: ResMut<'w, ThrustMomentCoupling>,
+    history: ResMut<'w, ThrustHistory>,
+    slipstream: Res<'w, SlipStreamField>,
+    pressure: ResMut<'w, PressureField>,
+    velocity_histogram: ResMut<'w, VelocityHistogram>,
+    tunnel: ResMut<'w, WindTunnelFlux>,
+    density_regulator: ResMut<'w, DensityRegulator>,
+    gas: GasDiagnostics<'w>,
+    heat: ResMut<'w, HeatGenerated>,
+    heat_capacity: Option<Res<'w, HeatCapacity>>,
+    fourier: ResMut<'w, FourierAnalysis>,
+    compression: ResMut<'w, CompressionRatio>,
+}
+
+// The particle fluid's kinetic theory statistics and its measured collisions
+#[derive(SystemParam)]
+struct GasDiagnostics<'w> {
+    statistics: Res<'w, ParticleStatistics>,
+    collisions: ResMut<'w, ParticleParticleCollisionStats>,
+}
+
+// Fluid and surroundings the controller reduces thrust and coefficients against, and the
+// viscous losses it reports
+#[derive(SystemParam)]
+struct Ambient<'w> {
+    fluid: Res<'w, FluidDensity>,
+    wind: Res<'w, WindProfile>,
+    ground: Res<'w, GroundPlane>,
+    dissipation: ResMut<'w, ViscousDissipation>,
+    reynolds: Res<'w, ReynoldsNumber>,
+    duct: Option<ResMut<'w, DuctThrust>>,
+}
+
+// Where and how completed pitches are written
+#[derive(SystemParam)]
+struct SweepOutput<'w> {
+    logger: Res<'w, DataLogger>,
+    coefficients: ResMut<'w, PropellerCoefficients>,
+    plot: Res<'w, EfficiencyPlotConfig>,
+    acoustics: ResMut<'w, PropellerAcoustics>,
+    metrics: Res<'w, PropellerMetrics>,
+    heatmap: Res<'w, BladeHeatmap>,
+}
+
+// Blade as a rod pivoting at the hub whose mass per length follows the chord, tapering
+// linearly to taper_ratio at the tip. A taper ratio of 1 is the uniform rod, m L^2 / 3.
+fn compute_moi(blade_length: f32, blade_mass: f32, taper_ratio: f32) -> f32 {
+    blade_mass * blade_length * blade_length * (1.0 + 3.0 * taper_ratio) / (6.0 * (1.0 + taper_ratio))
+}
+
+fn update_propeller_moi(mut hub_query: Query<&mut PropellerHub>, blade_query: Query<&PropellerBlade>, geometry: Res<PropellerGeometry>) {
+    for mut hub in hub_query.iter_mut() {
+        hub.moi = hub.hub_geometry.moi();
+    }
+    for blade in blade_query.iter() {
+        if let Ok(mut hub) = hub_query.get_mut(blade.hub) {
+            let blade_moi = compute_moi(blade.length, hub.mass, geometry.taper_ratio);
+            hub.moi += blade_moi;
+        }
+    }
+}
+
+fn transition_phase(mut phase: ResMut<SimulationPhase>, mut hub_query: Query<(&mut PropellerHub, &mut HubGovernor)>, mut diagnostics: TrialDiagnostics, mut governor_state: ResMut<GovernorState>,
+mut state: ResMut<SimulationState>, config: Res<SimConfig>, mut dissipation: ResMut<ViscousDissipation>,
+mut collision_counts: Query<&mut CollisionCount>, duct: Option<ResMut<DuctThrust>>) {
+    match *phase {
+        SimulationPhase::Warmup { steps } if steps + 1 < config.steps(config.warmup_duration) => {
+            *phase = SimulationPhase::Warmup { steps: steps + 1 };
+        }
+        SimulationPhase::Warmup { .. } => {
+            // start collecting from a clean slate, whatever the blades hit while spinning up is dropped
+            for (mut hub, mut hub_governor) in hub_query.iter_mut() {
+                hub_governor.work = 0.0;
+                hub.total_vertical_impulse = 0.0;
+                hub.total_x_impulse = 0.0;
+                hub.total_z_impulse = 0.0;
+                hub.total_reaction_torque = 0.0;
+                hub.total_world_impulse = Vec3::ZERO;
+                hub.peak_angular_v = hub.angular_v;
+                hub.min_angular_v = hub.angular_v;
+                hub.peak_vibration = 0.0;
+            }
+            diagnostics.blade_load.reset();
+            diagnostics.ripple.reset();
+            diagnostics.history.clear();
+            if let Some((hub, _)) = hub_query.iter().next() {
+                diagnostics.history.start_rotation = hub.rotation_direction.sign() * hub.rotation_z.to_radians();
+            }
+            *diagnostics.coupling = ThrustMomentCoupling::default();
+            diagnostics.pressure.cells.clear();
+            diagnostics.velocity_histogram.clear();
+            diagnostics.density_regulator.reset_cv();
+            diagnostics.compression.reset();
+            diagnostics.gas.collisions.reset();
+            if let Some(mut duct) = duct {
+                duct.reset();
+            }
+            dissipation.energy = 0.0;
+            for mut count in collision_counts.iter_mut() {
+                *count = CollisionCount::default();
+            }
+            diagnostics.heat.0 = 0.0;
+            diagnostics.tunnel.inflow_momentum = Vec3::ZERO;
+            diagnostics.tunnel.outflow_momentum = Vec3::ZERO;
+            governor_state.work = 0.0;
+            state.time_elapsed = 0.0;
+            *phase = SimulationPhase::Collecting { steps: 0 };
+        }
+        SimulationPhase::Collecting { steps } => {
+            let steps = steps + 1;
+            state.time_elapsed = steps as f32 * config.fixed_timestep;
+            *phase = if steps >= config.steps(config.trial_duration) { SimulationPhase::Resetting } else { SimulationPhase::Collecting { steps } };
+        }
+        SimulationPhase::Resetting => {}
+    }
+}
+
+fn controller(mut hub_query: Query<(Entity, &mut PropellerHub, &Transform, &mut HubGovernor)>, mut blade_query: Query<&mut PropellerBlade>, mut part_query: Query<(&mut Transform, &mut Particle, Option<&CollisionCount>), Without<PropellerHub>>, mut phase: ResMut<SimulationPhase>,
+mut diagnostics: TrialDiagnostics, mut output: SweepOutput, mut state: ResMut<SimulationState>, config: Res<SimConfig>,
+trial_count: Res<TrialCount>, geometry: Res<PropellerGeometry>, governor: Res<RotorGovernor>, mut governor_state: ResMut<GovernorState>,
+mut rng: ResMut<SimRng>, mut thrust_control: ThrustControl, mut exit: EventWriter<AppExit>, mut ambient: Ambient){
+    let state = &mut *state;
+    
+    if matches!(*phase, SimulationPhase::Resetting) {
+
+        // average force on each station over the trial
+        let loads: Vec<f32> = diagnostics.blade_load.stations.iter().map(|impulse| impulse / state.time_elapsed).collect();
+        let (tip_deflection, root_moment) = diagnostics.deformation.solve(&loads);
+        info!("Tip deflection: {}, root moment: {}", tip_deflection, root_moment);
+        diagnostics.blade_load.reset();
+
+        let ripple_amplitude = diagnostics.ripple.ripple_amplitude();
+        info!("ripple_amplitude: {}, ripple_frequency_hz: {}", ripple_amplitude, diagnostics.ripple.ripple_frequency_hz());
+        if ripple_amplitude > 0.5 {
+            warn!("Thrust ripple above 50%, particle count is too low for a smooth thrust estimate");
+        }
+        diagnostics.ripple.reset();
+
+        let trial_pitch = state.current_pitch(&config);
+        info!("Thrust mean: {}, standard deviation: {}", diagnostics.history.mean(), diagnostics.history.std_dev());
+        state.thrust_std_row.push(diagnostics.history.std_dev());
+        let (trial, history) = (state.trial, diagnostics.history.clone());
+        output.logger.write_file("thrust time series", move |dir| append_thrust_timeseries(&dir.join(format!("thrust_timeseries_pitch{}.csv", trial_pitch)), trial, &history));
+        let hub_count = hub_query.iter().count().max(1) as f32;
+        let fundamental_hz = hub_query.iter().map(|(_, hub, _, _)| hub.angular_v.abs() / 360.0).sum::<f32>() / hub_count;
+        *diagnostics.fourier = FourierAnalysis::compute(&diagnostics.history.samples, fundamental_hz);
+        info!("Thrust harmonics of {} Hz (amplitude, phase): {:?}", diagnostics.fourier.fundamental_hz, diagnostics.fourier.harmonics);
+        // referenced to the rotor angle the trial started at, so the trials' phases can be averaged
+        let start = diagnostics.history.start_rotation;
+        state.harmonics_row.push(diagnostics.fourier.harmonics.iter().enumerate().map(|(h, &(amplitude, phase))| (amplitude, phase - (h + 1) as f32 * start)).collect());
+        state.spectrum_peak_row.push((diagnostics.fourier.peak_hz, diagnostics.fourier.peak_off_harmonic));
+        diagnostics.history.clear();
+
+        let global_trial = state.pitch_index * trial_count.0 + state.trial + 1;
+        let histogram = diagnostics.velocity_histogram.clone();
+        output.logger.write_file("velocity distribution", move |dir| write_velocity_distribution(&dir.join(format!("velocity_dist_trial_{}.csv", global_trial)), &histogram));
+        state.speed_stats_row.push(diagnostics.velocity_histogram.statistics());
+        state.density_cv_row.push(diagnostics.density_regulator.mean_cv());
+        diagnostics.density_regulator.reset_cv();
+        info!("Compression ratio ahead of / behind the blades: {}", diagnostics.compression.mean());
+        state.compression_row.push(diagnostics.compression.mean());
+        diagnostics.compression.reset();
+        let collision_stats = &diagnostics.gas.collisions;
+        match collision_stats.mean_free_path_ratio(diagnostics.gas.statistics.mean_free_path) {
+            Some(ratio) => info!("Mean time between particle collisions: {}, mean free path measured: {}, measured / kinetic theory: {}",
+                collision_stats.mean_time_between_collisions, collision_stats.effective_mean_free_path, ratio),
+            None => info!("No repeated particle-particle collisions to measure the mean free path from"),
+        }
+        diagnostics.gas.collisions.reset();
+        diagnostics.velocity_histogram.clear();
+
+        let shaft_power = match *governor {
+            RotorGovernor::ConstantPower(power) => power,
+            RotorGovernor::ConstantRPM { .. } => governor_state.work / state.time_elapsed,
+        };
+        state.power_row.push(shaft_power);
+        let shaft_work = shaft_power * state.time_elapsed;
+        let drag_fraction = if shaft_work > 0.0 { ambient.dissipation.energy / shaft_work } else { 0.0 };
+        info!("Tip Reynolds number: {}, viscous drag took {} of the input power", ambient.reynolds.0, drag_fraction);
+        state.drag_fraction_row.push(drag_fraction);
+        ambient.dissipation.energy = 0.0;
+        state.slipstream_row.push(diagnostics.slipstream.mean_axial_velocity());
+        // spread over the whole fluid, it shifts the equilibrium velocity distribution
+        match diagnostics.heat_capacity.as_deref() {
+            Some(capacity) => {
+                let fluid_mass: f32 = part_query.iter().map(|(_, part, _)| part.mass).sum();
+                info!("Collision heat: {} J, temperature rise: {} K", diagnostics.heat.0, diagnostics.heat.0 / (capacity.0 * fluid_mass));
+            }
+            None => info!("Collision heat: {} J", diagnostics.heat.0),
+        }
+        diagnostics.heat.0 = 0.0;
+        let stats = &diagnostics.gas.statistics;
+        state.particle_stats_row.push(Vec3::new(stats.number_density, stats.rms_speed, stats.mean_free_path));
+        let blade_hits = blade_hit_statistics(part_query.iter().filter_map(|(_, _, count)| count));
+        let particle_hits = part_query.iter().filter_map(|(_, _, count)| count).map(|count| count.particle_hits as f32).sum::<f32>() / part_query.iter().count().max(1) as f32;
+        info!("Blade hits per particle mean: {}, max: {}, std dev: {}; particle hits mean: {}", blade_hits.x, blade_hits.y, blade_hits.z, particle_hits);
+        state.blade_hits_row.push(blade_hits);
+        *governor_state = GovernorState::default();
+
+        let trial_time = state.time_elapsed;
+        state.time_elapsed = 0.0;
+        state.trial += 1;
+
+        // every rotor's trial impulse, averaged into data_row and kept per rotor for the CSV
+        let mut pitch = config.first_pitch();
+        let mut impulses = Vec::new();
+        let mut rotor_pitches = Vec::new();
+        let mut rev_per_sec = Vec::new();
+        let mut rotor_powers = Vec::new();
+        let mut lateral = Vec2::ZERO;
+        let mut world_impulse = Vec3::ZERO;
+        let mut torque = 0.0;
+        let mut signed_torques = Vec::new();
+        let mut angular_v_range = (f32::NEG_INFINITY, f32::INFINITY);
+        let mut peak_vibration: f32 = 0.0;
+        for (hub_entity, mut prop, hub_transform, mut hub_governor) in hub_query.iter_mut(){
+            rotor_powers.push(match *governor {
+                RotorGovernor::ConstantPower(power) => power,
+                RotorGovernor::ConstantRPM { .. } => hub_governor.work / trial_time,
+            });
+            hub_governor.reset();
+            pitch = hub_pitch(hub_entity, blade_query.iter()).unwrap_or(config.first_pitch());
+            let ground_factor = if ambient.ground.enabled {
+                compute_ground_effect_factor(hub_transform.translation.y - ambient.ground.height, geometry.span)
+            } else {
+                1.0
+            };
+            impulses.push(prop.total_vertical_impulse * ground_factor);
+            rotor_pitches.push(pitch);
+            lateral += Vec2::new(prop.total_x_impulse, prop.total_z_impulse);
+            world_impulse += prop.total_world_impulse;
+            torque += prop.total_reaction_torque;
+            signed_torques.push(prop.rotation_direction.sign() * prop.total_reaction_torque);
+            angular_v_range = (angular_v_range.0.max(prop.peak_angular_v), angular_v_range.1.min(prop.min_angular_v));
+            peak_vibration = peak_vibration.max(prop.peak_vibration);
+            prop.peak_vibration = 0.0;
+            rev_per_sec.push(prop.angular_v / 360.0);
+            prop.rotation_z = 0.0;
+            prop.old_rotation_z = 0.0;
+            prop.angular_v = config.start_prop_velocity;
+            prop.total_vertical_impulse = 0.0;
+            prop.total_x_impulse = 0.0;
+            prop.total_z_impulse = 0.0;
+            prop.total_reaction_torque = 0.0;
+            prop.total_world_impulse = Vec3::ZERO;
+            prop.peak_angular_v = config.start_prop_velocity;
+            prop.min_angular_v = config.start_prop_velocity;
+        }
+        if diagnostics.transient.recording {
+            diagnostics.transient.finish(pitch, &output.logger);
+        }
+
+        // moments normalised by thrust times blade length, zero for a symmetric load
+        let total_impulse: f32 = impulses.iter().sum();
+        let reference = total_impulse * geometry.span;
+        if reference != 0.0 {
+            info!("Pitching moment coefficient: {}, rolling moment coefficient: {}", diagnostics.coupling.mx / reference, diagnostics.coupling.mz / reference);
+        }
+        *diagnostics.coupling = ThrustMomentCoupling::default();
+
+        // Bernoulli cross-check of the impulse thrust, taken at the first rotor
+        if let Some((_, _, hub_transform, _)) = hub_query.iter().next() {
+            if let Some(delta_p) = diagnostics.pressure.disk_pressure_difference(hub_transform.translation, geometry.span, ambient.fluid.density_kg_per_m3) {
+                info!("Disk pressure difference: {} Pa, pressure thrust: {} N", delta_p, delta_p * output.metrics.disk_area);
+            }
+        }
+        diagnostics.pressure.cells.clear();
+
+        // fluid leaving with more downward momentum than it arrived with pushed up on the rotors
+        if config.boundary_conditions.iter().any(|c| matches!(c, BoundaryCondition::WindTunnel { .. })) {
+            let deficit = diagnostics.tunnel.inflow_momentum - diagnostics.tunnel.outflow_momentum;
+            info!("Wind tunnel momentum deficit thrust: {} N, impulse thrust: {} N", deficit.y / config.trial_duration, total_impulse / config.trial_duration);
+        }
+        diagnostics.tunnel.inflow_momentum = Vec3::ZERO;
+        diagnostics.tunnel.outflow_momentum = Vec3::ZERO;
+
+        let rotor_count = impulses.len().max(1) as f32;
+        state.data_row.push(total_impulse / rotor_count);
+        state.rev_per_sec_row.push(rev_per_sec.iter().sum::<f32>() / rotor_count);
+        state.lateral_row.push(lateral / rotor_count);
+        state.world_impulse_row.push(world_impulse / rotor_count);
+        state.torque_row.push(torque / rotor_count);
+        state.angular_v_range_row.push(angular_v_range);
+        state.vibration_row.push(peak_vibration);
+        if let Some(duct) = ambient.duct.as_mut() {
+            info!("Duct thrust: {} N, rotor thrust: {} N, duct wall hits: {}", duct.impulse.y / rotor_count / config.trial_duration,
+                total_impulse / rotor_count / config.trial_duration, duct.wall_hits);
+            state.duct_row.push((duct.impulse.y / rotor_count, duct.wall_hits));
+            duct.reset();
+        }
+
+        let blades_per_rotor = (blade_query.iter().count() as f32 / rotor_count).round() as u32;
+        let trial_acoustics = PropellerAcoustics::compute(total_impulse / rotor_count / config.trial_duration, rev_per_sec.iter().sum::<f32>() / rotor_count,
+            geometry.span * config.simulation_scale.length_m_per_unit, output.metrics.disk_area, ambient.fluid.density_kg_per_m3, blades_per_rotor, config.speed_of_sound);
+        info!("SPL estimate: {} dB, blade passage frequency: {} Hz", trial_acoustics.spl_db, trial_acoustics.bpf_hz);
+        trial_acoustics.warn_if_compressible();
+        state.rotor_pitches = rotor_pitches;
+        state.rotor_rows.resize(impulses.len(), Vec::new());
+        for (row, impulse) in state.rotor_rows.iter_mut().zip(impulses) {
+            row.push(impulse);
+        }
+        state.rotor_speed_rows.resize(rotor_powers.len(), Vec::new());
+        for ((row, &speed), power) in state.rotor_speed_rows.iter_mut().zip(&rev_per_sec).zip(rotor_powers) {
+            row.push((speed, power));
+        }
+        state.rotor_torque_rows.resize(signed_torques.len(), Vec::new());
+        for (row, torque) in state.rotor_torque_rows.iter_mut().zip(signed_torques) {
+            row.push(torque);
+        }
+
+        if state.trial == trial_count.0 {
+            let conditions = PitchConditions {
+                density: ambient.fluid.density_kg_per_m3,
+                inflow_speed: ambient.wind.inflow_speed(),
+                span: geometry.span,
+                disk_area: output.metrics.disk_area,
+                blades_per_rotor,
+            };
+            let summary = state.pitch_summary(&config, pitch, trial_count.0, &conditions);
+            let (mean_thrust, mean_power) = (summary.mean_thrust, summary.mean_power);
+            *output.coefficients = summary.coefficients;
+            *output.acoustics = summary.acoustics;
+            state.results.extend(summary.results);
+            for record in summary.records {
+                output.logger.send(record);
+            }
+            state.pitch_index += 1;
+            // the pid search takes as many steps as the sweep has points
+            let optimizing = thrust_control.optimizer.mode == OptimizerMode::GoldenSection;
+            let next_pitch = match config.pitch_control {
+                _ if optimizing => {
+                    let efficiency = if mean_power > 0.0 { mean_thrust / mean_power } else { 0.0 };
+                    let next = thrust_control.search.record(efficiency, thrust_control.optimizer.tolerance);
+                    info!("Golden-section evaluation {}: pitch {} gives {} N/W, bracket [{}, {}]",
+                        thrust_control.search.evaluations, pitch, efficiency, thrust_control.search.a, thrust_control.search.b);
+                    let search = *thrust_control.search;
+                    output.logger.write_file("optimizer log", move |dir| append_optimizer_log(&dir.join(OPTIMIZER_LOG_PATH), &search, pitch, efficiency));
+                    next
+                }
+                PitchControl::Sweep => config.pitch_at(state.pitch_index),
+                PitchControl::Pid => config.pitch_at(state.pitch_index).map(|_| {
+                    let next = thrust_control.state.next_pitch(&thrust_control.controller, mean_thrust, pitch);
+                    info!("Thrust {} N against target {} N, next pitch {}", mean_thrust, thrust_control.controller.target_thrust, next);
+                    next
+                }),
+            };
+            if let Some(next_pitch) = next_pitch {
+                if config.pitch_control == PitchControl::Sweep && !optimizing {
+                    assert!(next_pitch > pitch, "pitch must increase monotonically, {} -> {}", pitch, next_pitch);
+                } else {
+                    state.adaptive_pitch = Some(next_pitch);
+                }
+                for mut blade in blade_query.iter_mut() {
+                    blade.pitch = next_pitch;
+                }
+            }
+            diagnostics.transient.start();
+            state.data_row.clear();
+            state.rev_per_sec_row.clear();
+            state.power_row.clear();
+            state.drag_fraction_row.clear();
+            state.thrust_std_row.clear();
+            state.slipstream_row.clear();
+            state.particle_stats_row.clear();
+            state.blade_hits_row.clear();
+            state.harmonics_row.clear();
+            state.spectrum_peak_row.clear();
+            state.lateral_row.clear();
+            state.world_impulse_row.clear();
+            state.torque_row.clear();
+            state.speed_stats_row.clear();
+            state.density_cv_row.clear();
+            state.compression_row.clear();
+            state.angular_v_range_row.clear();
+            state.vibration_row.clear();
+            state.duct_row.clear();
+            state.rotor_rows.clear();
+            state.rotor_speed_rows.clear();
+            state.rotor_torque_rows.clear();
+            state.trial = 0;
+
+            //once the sweep reaches pitch_end, quit program
+            if next_pitch.is_none() {
+                let (plot, results) = (output.plot.clone(), state.results.clone());
+                output.logger.write_file("efficiency plot", move |dir| write_efficiency_svg(dir, &plot, &results));
+                let heatmap = output.heatmap.clone();
+                output.logger.write_file("blade heatmap", move |dir| write_blade_heatmap(&dir.join(HEATMAP_PATH), &heatmap));
+                let mut report = SimulationReport::new(config.clone(), state.results.clone());
+                if optimizing {
+                    if let Some((pitch, efficiency)) = thrust_control.search.best() {
+                        info!("Golden-section optimum after {} evaluations: pitch {}, {} N/W", thrust_control.search.evaluations, pitch, efficiency);
+                        report.optimal_pitch = Some(pitch);
+                        report.optimal_efficiency = Some(efficiency);
+                    }
+                }
+                output.logger.write_file("simulation report", move |dir| write_simulation_report(&dir.join(REPORT_PATH), &report));
+                state.finished = true;
+                exit.send(AppExit);
+            }
+        }
+
+        let rng = &mut rng.0;
+        for (mut transform, mut part, _) in part_query.iter_mut(){
+            transform.translation = random_domain_position(rng, part.domain_center, &config.bounding_box);
+            part.velocity = config.particle_init.velocity_distribution.sample(&ambient.fluid, rng);
+        }
+
+        *phase = SimulationPhase::Warmup { steps: 0 };
+    }
+ 
+}
+
+// pitch_deg, trial_1..trial_N, then the named summary columns
+fn csv_header(trial_count: u32, columns: &[&str]) -> Vec<String> {
+    let mut header = vec!["pitch_deg".to_string()];
+    header.extend((1..=trial_count).map(|n| format!("trial_{}", n)));
+    header.extend(columns.iter().map(|name| name.to_string()));
+    header
+}
+
+fn append_to_csv(output: &CsvOutputConfig, header: &[String], data: &[f32]) -> Result<(), Box<dyn Error>> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&output.file_path)?;
+    let is_empty = file.seek(SeekFrom::End(0))? == 0;
+    let mut wtr = WriterBuilder::new().delimiter(output.delimiter as u8).from_writer(file);
+
+    // header only goes at the top of a fresh file
+    if output.write_header && is_empty {
+        wtr.write_record(header)?;
+    }
+
+    let string_data: Vec<String> = data.iter().map(|&num| num.to_string()).collect();
+    let string_refs: Vec<&str> = string_data.iter().map(|s| s.as_str()).collect();
+
+    // Write the vector as a single CSV record
+    wtr.write_record(&string_refs)?;
+
+    wtr.flush()?;
+    Ok(())
+}
+
+// One JSON object per line, written in a single call so a killed run never leaves a partial record
+fn append_to_json(file_path: &str, record: &JsonRecord) -> Result<(), Box<dyn Error>> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(file_path)?;
+    file.write_all(line.as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+// Runs before the controller so strikes land in the file of the trial they happened in
+fn log_collisions(mut collisions: EventReader<BladeParticleCollision>, state: Res<SimulationState>, config: Res<SimConfig>, logger: Res<DataLogger>) {
+    if !config.log_collisions {
+        collisions.clear();
+        return;
+    }
+    let events: Vec<BladeParticleCollision> = collisions.read().cloned().collect();
+    if events.is_empty() {
+        return;
+    }
+    let pitch = state.current_pitch(&config);
+    let file_name = format!("collisions_{}_{}.csv", pitch, state.trial);
+    logger.write_file("collision log", move |dir| append_collisions(&dir.join(file_name), &events));
+}
+
+fn append_collisions(file_path: &std::path::Path, events: &[BladeParticleCollision]) -> Result<(), Box<dyn Error>> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(file_path)?;
+    let is_empty = file.seek(SeekFrom::End(0))? == 0;
+    let mut wtr = Writer::from_writer(file);
+    if is_empty {
+        wtr.write_record(["particle", "x", "y", "z", "impulse_x", "impulse_y", "impulse_z", "blade_rotation", "pitch"])?;
+    }
+    for event in events {
+        wtr.write_record(&[
+            format!("{:?}", event.particle_entity),
+            event.position.x.to_string(),
+            event.position.y.to_string(),
+            event.position.z.to_string(),
+            event.impulse.x.to_string(),
+            event.impulse.y.to_string(),
+            event.impulse.z.to_string(),
+            event.blade_rotation_deg.to_string(),
+            event.pitch_deg.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+const HEATMAP_PATH: &str = "blade_heatmap.csv";
+
+// Header of bin centres as a fraction of blade length, then the strike count of each
+fn write_blade_heatmap(file_path: &std::path::Path, heatmap: &BladeHeatmap) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(file_path)?;
+    wtr.write_record((0..heatmap.num_bins).map(|i| ((i as f32 + 0.5) / heatmap.num_bins as f32).to_string()))?;
+    wtr.write_record(heatmap.bins.iter().map(|count| count.to_string()))?;
+    wtr.flush()?;
+    Ok(())
+}
+
+// One file per pitch, every trial appended under its own trial number
+fn write_velocity_distribution(file_path: &std::path::Path, histogram: &VelocityHistogram) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(file_path)?;
+    wtr.write_record(["speed", "probability_density"])?;
+    for (speed, density) in histogram.density() {
+        wtr.write_record(&[speed.to_string(), density.to_string()])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// UnstructuredGrid with a vertex cell per particle and a quad per blade. Blade points carry
+// zero velocity and particle_id -1.
+fn write_vtu(file_path: &std::path::Path, particles: &[(u32, Vec3, Vec3)], blades: &[[Vec3; 4]]) -> Result<(), Box<dyn Error>> {
+    let point_count = particles.len() + 4 * blades.len();
+    let cell_count = particles.len() + blades.len();
+    let mut points = String::new();
+    let mut velocity = String::new();
+    let mut speed = String::new();
+    let mut ids = String::new();
+    for &(id, position, v) in particles {
+        points += &format!("{} {} {} ", position.x, position.y, position.z);
+        velocity += &format!("{} {} {} ", v.x, v.y, v.z);
+        speed += &format!("{} ", v.length());
+        ids += &format!("{} ", id);
+    }
+    for corner in blades.iter().flatten() {
+        points += &format!("{} {} {} ", corner.x, corner.y, corner.z);
+        velocity += "0 0 0 ";
+        speed += "0 ";
+        ids += "-1 ";
+    }
+
+    let mut connectivity: Vec<String> = (0..particles.len()).map(|i| i.to_string()).collect();
+    let mut offsets: Vec<String> = (1..=particles.len()).map(|i| i.to_string()).collect();
+    // VTK_VERTEX is 1, VTK_QUAD 9
+    let mut types = vec!["1"; particles.len()];
+    for b in 0..blades.len() {
+        let first = particles.len() + 4 * b;
+        connectivity.extend((first..first + 4).map(|i| i.to_string()));
+        offsets.push((particles.len() + 4 * (b + 1)).to_string());
+        types.push("9");
+    }
+
+    let mut xml = String::new();
+    xml += "<?xml version=\"1.0\"?>\n<VTKFile type=\"UnstructuredGrid\" version=\"0.1\" byte_order=\"LittleEndian\">\n<UnstructuredGrid>\n";
+    xml += &format!("<Piece NumberOfPoints=\"{}\" NumberOfCells=\"{}\">\n", point_count, cell_count);
+    xml += "<PointData Scalars=\"speed\" Vectors=\"velocity\">\n";
+    xml += &format!("<DataArray type=\"Float32\" Name=\"velocity\" NumberOfComponents=\"3\" format=\"ascii\">{}</DataArray>\n", velocity);
+    xml += &format!("<DataArray type=\"Float32\" Name=\"speed\" format=\"ascii\">{}</DataArray>\n", speed);
+    xml += &format!("<DataArray type=\"Int64\" Name=\"particle_id\" format=\"ascii\">{}</DataArray>\n", ids);
+    xml += "</PointData>\n<Points>\n";
+    xml += &format!("<DataArray type=\"Float32\" NumberOfComponents=\"3\" format=\"ascii\">{}</DataArray>\n", points);
+    xml += "</Points>\n<Cells>\n";
+    xml += &format!("<DataArray type=\"Int64\" Name=\"connectivity\" format=\"ascii\">{}</DataArray>\n", connectivity.join(" "));
+    xml += &format!("<DataArray type=\"Int64\" Name=\"offsets\" format=\"ascii\">{}</DataArray>\n", offsets.join(" "));
+    xml += &format!("<DataArray type=\"UInt8\" Name=\"types\" format=\"ascii\">{}</DataArray>\n", types.join(" "));
+    xml += "</Cells>\n</Piece>\n</UnstructuredGrid>\n</VTKFile>\n";
+    std::fs::write(file_path, xml)?;
+    Ok(())
+}
+
+fn append_thrust_timeseries(file_path: &std::path::Path, trial: u32, history: &ThrustHistory) -> Result<(), Box<dyn Error>> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(file_path)?;
+    let is_empty = file.seek(SeekFrom::End(0))? == 0;
+    let mut wtr = Writer::from_writer(file);
+    if is_empty {
+        wtr.write_record(["trial", "time", "thrust", "tip_mach"])?;
+    }
+    for (&(t, thrust), tip_mach) in history.samples.iter().zip(&history.tip_mach) {
+        wtr.write_record(&[trial.to_string(), t.to_string(), thrust.to_string(), tip_mach.to_string()])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// One finished pitch of the sweep, thrusts in N per rotor
+#[derive(Serialize, Clone)]
+pub struct PitchResult {
+    pub pitch: f32,
+    pub trial_thrusts: Vec<f32>,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub ct: f32,
+    pub cp: f32,
+    // J CT / CP with inflow, the figure of merit for a static rotor
+    pub efficiency: f32,
+    // reaction torque with each rotor's spin sign, N m, over the whole array outside a parallel batch
+    pub net_torque: f32,
+}
+
+impl PitchResult {
+    fn new(pitch: f32, trial_thrusts: Vec<f32>, coefficients: &PropellerCoefficients, net_torque: f32) -> Self {
+        let mean = trial_thrusts.iter().sum::<f32>() / trial_thrusts.len() as f32;
+        let variance = trial_thrusts.iter().map(|t| (t - mean).powi(2)).sum::<f32>() / trial_thrusts.len() as f32;
+        PitchResult {
+            pitch,
+            trial_thrusts,
+            mean,
+            std_dev: variance.sqrt(),
+            ct: coefficients.ct,
+            cp: coefficients.cp,
+            efficiency: if coefficients.advance_ratio > 0.0 && coefficients.cp != 0.0 {
+                coefficients.advance_ratio * coefficients.ct / coefficients.cp
+            } else {
+                coefficients.figure_of_merit
+            },
+            net_torque,
+        }
+    }
+}
+
+// Everything needed to archive or compare a sweep, written once it finishes
+#[derive(Serialize)]
+struct SimulationReport {
+    // set at build time when a vergen build script provides it
+    git_hash: Option<String>,
+    config: SimConfig,
+    results: Vec<PitchResult>,
+    best_pitch: f32,
+    peak_thrust: f32,
+    peak_efficiency: f32,
+    // golden-section result, thrust / shaft power in N/W
+    optimal_pitch: Option<f32>,
+    optimal_efficiency: Option<f32>,
+}
+
+const REPORT_PATH: &str = "report.json";
+
+impl SimulationReport {
+    fn new(config: SimConfig, results: Vec<PitchResult>) -> Self {
+        let peak_thrust = results.iter().map(|r| r.mean).fold(f32::NEG_INFINITY, f32::max);
+        let best = results.iter().max_by(|a, b| a.efficiency.total_cmp(&b.efficiency));
+        SimulationReport {
+            git_hash: option_env!("VERGEN_GIT_SHA").map(str::to_string),
+            best_pitch: best.map_or(config.first_pitch(), |r| r.pitch),
+            peak_efficiency: best.map_or(0.0, |r| r.efficiency),
+            peak_thrust: if results.is_empty() { 0.0 } else { peak_thrust },
+            optimal_pitch: None,
+            optimal_efficiency: None,
+            config,
+            results,
+        }
+    }
+}
+
+fn write_simulation_report(file_path: &std::path::Path, report: &SimulationReport) -> Result<(), Box<dyn Error>> {
+    let file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+    serde_json::to_writer_pretty(file, report)?;
+    Ok(())
+}
+
+// The report's efficiency on the left axis and CT on the right, against pitch
+fn write_efficiency_svg(output_dir: &std::path::Path, plot: &EfficiencyPlotConfig, results: &[PitchResult]) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (plot.width as f32, plot.height as f32);
+    let (left, right, top, bottom) = (80.0, width - 80.0, 40.0, height - 60.0);
+
+    let pitch_min = results.iter().map(|r| r.pitch).fold(f32::MAX, f32::min);
+    let pitch_max = results.iter().map(|r| r.pitch).fold(f32::MIN, f32::max);
+    let (pitch_min, pitch_max) = if results.is_empty() { (0.0, 1.0) } else if pitch_max > pitch_min { (pitch_min, pitch_max) } else { (pitch_min - 1.0, pitch_max + 1.0) };
+    // both axes start at zero
+    let axis_max = |values: &mut dyn Iterator<Item = f32>| {
+        let max = values.fold(0.0, f32::max);
+        if max > 0.0 { max * 1.1 } else { 1.0 }
+    };
+    let efficiency_max = axis_max(&mut results.iter().map(|r| r.efficiency));
+    let ct_max = axis_max(&mut results.iter().map(|r| r.ct));
+
+    let x = |pitch: f32| left + (pitch - pitch_min) / (pitch_max - pitch_min) * (right - left);
+    let y = |value: f32, max: f32| bottom - value / max * (bottom - top);
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">\n", plot.width, plot.height);
+    svg += &format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", plot.width, plot.height);
+
+    let ticks = 5;
+    for i in 0..=ticks {
+        let frac = i as f32 / ticks as f32;
+        let gy = bottom - frac * (bottom - top);
+        let gx = left + frac * (right - left);
+        svg += &format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#ddd\"/>\n", left, gy, right, gy);
+        svg += &format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#ddd\"/>\n", gx, top, gx, bottom);
+        svg += &format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.3}</text>\n", left - 6.0, gy + 4.0, frac * efficiency_max);
+        svg += &format!("<text x=\"{}\" y=\"{}\" text-anchor=\"start\">{:.3}</text>\n", right + 6.0, gy + 4.0, frac * ct_max);
+        svg += &format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{:.1}</text>\n", gx, bottom + 18.0, pitch_min + frac * (pitch_max - pitch_min));
+    }
+    svg += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>\n", left, top, right - left, bottom - top);
+
+    svg += &format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">Pitch (deg)</text>\n", (left + right) / 2.0, height - 15.0);
+    svg += &format!("<text x=\"20\" y=\"{0}\" text-anchor=\"middle\" fill=\"steelblue\" transform=\"rotate(-90 20 {0})\">Efficiency (J CT / CP, figure of merit when static)</text>\n", (top + bottom) / 2.0);
+    svg += &format!("<text x=\"{0}\" y=\"{1}\" text-anchor=\"middle\" fill=\"darkorange\" transform=\"rotate(90 {0} {1})\">CT</text>\n", width - 20.0, (top + bottom) / 2.0);
+
+    let curves = [
+        (results.iter().map(|r| (x(r.pitch), y(r.efficiency, efficiency_max))).collect::<Vec<_>>(), "steelblue"),
+        (results.iter().map(|r| (x(r.pitch), y(r.ct, ct_max))).collect::<Vec<_>>(), "darkorange"),
+    ];
+    for (curve, color) in &curves {
+        let path: Vec<String> = curve.iter().map(|(px, py)| format!("{:.1},{:.1}", px, py)).collect();
+        svg += &format!("<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n", path.join(" "), color);
+        for (px, py) in curve {
+            svg += &format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\"/>\n", px, py, color);
+        }
+    }
+    svg += "</svg>\n";
+
+    std::fs::write(output_dir.join(&plot.file_path), svg)?;
+    Ok(())
+}
+
+fn write_startup_transient(file_path: &std::path::Path, samples: &[(f32, f32)]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(file_path)?;
+    wtr.write_record(["time", "angular_v"])?;
+    for &(t, angular_v) in samples {
+        wtr.write_record(&[t.to_string(), angular_v.to_string()])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// fn write_csv_no_header(file_path: &str, data: &Vec<f32>) -> Result<(), Box<dyn Error>> {
+//     let mut wtr = Writer::from_path(file_path)?;
+
+//     let string_data: Vec<String> = data.iter().map(|&num| num.to_string()).collect();
+//     let string_refs: Vec<&str> = string_data.iter().map(|s| s.as_str()).collect();
+
+//     // Write the vector as a single CSV record
+//     wtr.write_record(&string_refs)?;
+
+//     wtr.flush()?;
+//     Ok(())
+// }
+
+
+// Spawn particles with visible 3D spheres
+fn spawn_particles(
+    mut commands: Commands,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    fluid: Res<FluidDensity>,
+    config: Res<SimConfig>,
+    color_mode: Res<ParticleColorMode>,
+    mut pool: ResMut<ParticlePool>,
+    mut count: ResMut<ParticleCount>,
+    mut rng: ResMut<SimRng>,
+) {
+    let sphere_handle = meshes.map(|mut meshes| {
+        let sphere_mesh = Mesh::try_from(shape::Icosphere { radius: fluid.particle_radius, subdivisions: 4 })
+            .expect("Failed to create sphere mesh");
+        meshes.add(sphere_mesh)
+    });
+
+    // particle_count in every domain
+    let domains = config.domain_centers();
+    let spawned = config.particle_count * domains.len();
+    pool.capacity = spawned + config.max_particles;
+    for _ in 0..pool.capacity {
+        // every particle gets its own material so it can be recoloured independently
+        let particle_render = match (&sphere_handle, materials.as_mut()) {
+            (Some(mesh), Some(materials)) => Some((mesh.clone(), materials.add(StandardMaterial { base_color: color_mode.color_for(0.0), ..default() }))),
+            _ => None,
+        };
+        let entity = spawn_body(&mut commands, &particle_render, Transform::default(), ());
+        commands.entity(entity).insert(Visibility::Hidden);
+        pool.available.push_back(entity);
+    }
+
+    let rng = &mut rng.0;
+    let distribution = config.particle_init.velocity_distribution;
+    let mut square_speed_sum = 0.0;
+    for domain_center in domains.iter().flat_map(|&center| std::iter::repeat_n(center, config.particle_count)) {
+        //let velocity = Vec3::new(0.0, 0.0, 0.0);
+        let velocity = distribution.sample(&fluid, rng);
+        square_speed_sum += velocity.length_squared();
+        pool.acquire(
+            &mut commands,
+            &mut count,
+            Transform::from_translation(random_domain_position(rng, domain_center, &config.bounding_box)),
+            Particle {
+                velocity,
+                mass: fluid.particle_mass(),
+                domain_center,
+            },
+        );
+    }
+    if matches!(distribution, VelocityDist::MaxwellBoltzmann { .. }) && spawned > 0 {
+        let mean_energy = 0.5 * fluid.molecular_mass_kg * square_speed_sum / spawned as f32;
+        info!("Initial kinetic energy per molecule: {} J, 1.5 kT: {} J", mean_energy, 1.5 * BOLTZMANN * fluid.temperature_k);
+    }
+
+    for emitter in &config.emitters {
+        commands.spawn(emitter.clone());
+    }
+}
+
+fn emit_particles(
+    mut commands: Commands,
+    mut emitter_query: Query<&mut ParticleEmitter>,
+    mut pool: ResMut<ParticlePool>,
+    mut count: ResMut<ParticleCount>,
+    fluid: Res<FluidDensity>,
+    time: Res<Time>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = &mut rng.0;
+    for mut emitter in emitter_query.iter_mut() {
+        emitter.accumulated += emitter.rate_per_second * time.delta_seconds();
+        while emitter.accumulated >= 1.0 {
+            emitter.accumulated -= 1.0;
+            let spread = emitter.velocity_spread;
+            let velocity = emitter.initial_velocity + Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * spread;
+            let particle = Particle { velocity, mass: fluid.particle_mass(), domain_center: Vec3::ZERO };
+            if pool.acquire(&mut commands, &mut count, Transform::from_translation(emitter.position), particle).is_none() {
+                // pool exhausted, drop the backlog rather than bursting later
+                emitter.accumulated = 0.0;
+                break;
+            }
+        }
+    }
+}
+
+// Heat map recolouring does one material lookup per particle per frame (400 at the
+// default count). Instanced rendering with a per-instance colour would avoid this.
+fn update_particle_colors(
+    color_mode: Res<ParticleColorMode>,
+    query: Query<(&Particle, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // a uniform colour only needs applying when the mode changes
+    if matches!(*color_mode, ParticleColorMode::Uniform(_)) && !color_mode.is_changed() {
+        return;
+    }
+    if matches!(*color_mode, ParticleColorMode::CollisionRecency { .. }) {
+        return;
+    }
+    for (particle, handle) in query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = color_mode.color_for(particle.velocity.length());
+        }
+    }
+}
+
+fn stamp_collision_times(mut collisions: EventReader<BladeParticleCollision>, mut query: Query<&mut LastCollisionTime>, time: Res<Time>) {
+    for event in collisions.read() {
+        if let Ok(mut last) = query.get_mut(event.particle_entity) {
+            last.blade = Some(time.elapsed_seconds());
+        }
+    }
+}
+
+// Shows where the rotor has energised the fluid and how fast that spreads
+fn color_particles_by_recency(
+    color_mode: Res<ParticleColorMode>,
+    query: Query<(&LastCollisionTime, &Handle<StandardMaterial>), With<Particle>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    let ParticleColorMode::CollisionRecency { fade_seconds } = *color_mode else {
+        return;
+    };
+    let now = time.elapsed_seconds();
+    for (last, handle) in query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = match last.blade {
+                Some(struck_at) => {
+                    let fade = ((now - struck_at) / fade_seconds).clamp(0.0, 1.0);
+                    Color::rgb(1.0 - fade, 1.0 - fade, 1.0)
+                }
+                None => color_mode.color_for(0.0),
+            };
+        }
+    }
+}
+
+// Re-derive particle mass whenever the fluid is changed, e.g. between pitch sweeps
+fn update_particle_mass(fluid: Res<FluidDensity>, mut query: Query<&mut Particle>) {
+    if !fluid.is_changed() {
+        return;
+    }
+    let mass = fluid.particle_mass();
+    for mut particle in query.iter_mut() {
+        particle.mass = mass;
+    }
+}
+
+// Update particle movement each frame
+fn move_particles(mut query: Query<(&mut Transform, &mut Particle)>, gravity: Res<Gravity>, wind: Res<WindProfile>, time: Res<Time>,
+count: Res<ParticleCount>, threshold: Res<ParallelThreshold>, max_speed: Res<MaxParticleSpeed>, min_speed: Res<MinParticleSpeed>,
+mut overspeed: ResMut<OverspeedDiagnostic>, solver: Res<SolverMode>, fluid: Res<FluidDensity>, viscosity: Res<Viscosity>, drag: Res<ViscousDragEnabled>,
+mut dissipation: ResMut<ViscousDissipation>, temperature: Res<FluidTemperature>, sutherland: Option<Res<TemperatureDependentViscosity>>) {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    let dt = time.delta_seconds();
+    // Stokes drag force over velocity at a height, 0 when disabled
+    let drag_coefficient = |y: f32| {
+        if !drag.0 {
+            return 0.0;
+        }
+        let eta = match sutherland.as_deref() {
+            Some(sutherland) if sutherland.temperature_gradient.is_some() => sutherland.viscosity_at(sutherland.temperature_at(temperature.0, y)),
+            _ => viscosity.0,
+        };
+        6.0 * std::f32::consts::PI * eta * fluid.particle_radius
+    };
+    let clamped = AtomicU32::new(0);
+    // bits of a non-negative f32 order the same as the value, so fetch_max works on them
+    let fastest = AtomicU32::new(0);
+    let step = |(mut transform, mut particle): (Mut<Transform>, Mut<Particle>)| {
+        let drag_over_mass = drag_coefficient(transform.translation.y) / particle.mass;
+        // wind acts as a body force
+        let acceleration = |position: Vec3, velocity: Vec3| gravity.acceleration_at(position) + wind.wind_at(position) - drag_over_mass * velocity;
+        let (mut position, velocity) = solver.step(transform.translation, particle.velocity, dt, acceleration);
+        particle.velocity = velocity;
+        let speed = particle.velocity.length();
+        // a clamped particle moves with the clamped velocity, not the one the integrator reached
+        if speed > max_speed.0 {
+            particle.velocity *= max_speed.0 / speed;
+            position = transform.translation + particle.velocity * dt;
+            clamped.fetch_add(1, Ordering::Relaxed);
+            fastest.fetch_max(speed.to_bits(), Ordering::Relaxed);
+        } else if speed < min_speed.0 {
+            particle.velocity = Vec3::ZERO;
+            position = transform.translation;
+        }
+        transform.translation = position;
+    };
+    if count.0 > threshold.0 {
+        query.par_iter_mut().for_each(step);
+    } else {
+        query.iter_mut().for_each(step);
+    }
+    dissipation.power = query.iter().map(|(transform, particle)| drag_coefficient(transform.translation.y) * particle.velocity.length_squared()).sum::<f32>();
+    dissipation.energy += dissipation.power * dt;
+    overspeed.count = clamped.into_inner();
+    overspeed.max_speed_seen = f32::from_bits(fastest.into_inner());
+    if overspeed.count > OVERSPEED_REPORT_COUNT {
+        warn!("{} particles clamped to {} this step, fastest was {}", overspeed.count, max_speed.0, overspeed.max_speed_seen);
+    }
+}
+
+fn distance_between(a: &Transform, b: &Transform) -> f32 {
+    a.translation.distance(b.translation)
+}
+
+// Apply each axis' boundary condition to one particle, returning the condition
+// that takes it out of the domain, if any. Absorbed particles that respawn are
+// repositioned by the caller so the draw comes from the shared SimRng.
+fn apply_boundaries(boundaries: &BoundaryConditions, bounds: &BoundingBox, transform: &mut Transform, particle: &mut Particle) -> Option<BoundaryCondition> {
+    for i in 0..3 {
+        let half = bounds.half_extent[i];
+        let center = particle.domain_center[i];
+        let x = transform.translation[i] - center;
+        // a particle on the face itself only meets the wall while heading out through it
+        if x.abs() < half || (x.abs() == half && particle.velocity[i] * x <= 0.0) {
+            continue;
+        }
+        match boundaries.0[i] {
+            BoundaryCondition::Reflect => {
+                transform.translation[i] = center + x.signum() * half;
+                //println!("{}", transform.translation[i].to_string());
+                particle.velocity[i] *= -1.0;
+            }
+            BoundaryCondition::Wrap => {
+                transform.translation[i] = center + x - x.signum() * 2.0 * half;
+            }
+            condition @ (BoundaryCondition::Absorb { .. } | BoundaryCondition::OpenOutflow) => return Some(condition),
+            condition @ BoundaryCondition::WindTunnel { inflow_velocity, inflow_face, .. } => {
+                if x.signum() == downstream_sign(inflow_velocity, inflow_face) {
+                    return Some(condition);
+                }
+                // drifting back out upstream, keep it in the tunnel
+                transform.translation[i] = center + x.signum() * half;
+                particle.velocity[i] *= -1.0;
+            }
+        }
+    }
+    None
+}
+
+fn wall_collisions(mut commands: Commands, mut query: Query<(Entity, &mut Transform, &mut Particle)>, boundaries: Res<BoundaryConditions>,
+mut count: ResMut<ParticleCount>, mut outflow: ResMut<OutflowFlux>, threshold: Res<ParallelThreshold>, mut pool: ResMut<ParticlePool>,
+mut rng: ResMut<SimRng>, mut tunnel: ResMut<WindTunnelFlux>, bounds: Res<BoundingBox>) {
+    // particles leaving the domain, handled serially since they touch shared resources
+    let removed = std::sync::Mutex::new(Vec::new());
+    let step = |(entity, mut transform, mut particle): (Entity, Mut<Transform>, Mut<Particle>)| {
+        if let Some(condition) = apply_boundaries(&boundaries, &bounds, &mut transform, &mut particle) {
+            removed.lock().unwrap().push((entity, condition, particle.mass, particle.velocity, particle.domain_center));
+        }
+    };
+    if count.0 > threshold.0 {
+        query.par_iter_mut().for_each(step);
+    } else {
+        query.iter_mut().for_each(step);
+    }
+
+    // threads push in any order, sort so seeded runs draw respawns identically
+    let mut removed = removed.into_inner().unwrap();
+    removed.sort_by_key(|&(entity, ..)| entity);
+    for (entity, condition, mass, velocity, domain_center) in removed {
+        if matches!(condition, BoundaryCondition::Absorb { respawn: true }) {
+            if let Ok((_, mut transform, _)) = query.get_mut(entity) {
+                transform.translation = random_domain_position(&mut rng.0, domain_center, &bounds);
+            }
+            continue;
+        }
+        if matches!(condition, BoundaryCondition::OpenOutflow) {
+            outflow.mass += mass;
+            outflow.momentum += mass * velocity;
+            outflow.particles += 1;
+        }
+        if matches!(condition, BoundaryCondition::WindTunnel { .. }) {
+            tunnel.outflow_momentum += mass * velocity;
+        }
+        // back to the pool for emitters to reuse
+        pool.release(&mut commands, &mut count, entity);
+    }
+}
+
+// Feeds each WindTunnel boundary's stream from the pool, spread over the upstream face
+fn inject_wind_tunnel(mut commands: Commands, boundaries: Res<BoundaryConditions>, mut pool: ResMut<ParticlePool>, mut count: ResMut<ParticleCount>,
+mut tunnel: ResMut<WindTunnelFlux>, fluid: Res<FluidDensity>, time: Res<Time>, mut rng: ResMut<SimRng>, bounds: Res<BoundingBox>) {
+    let rng = &mut rng.0;
+    for condition in boundaries.0 {
+        let BoundaryCondition::WindTunnel { inflow_velocity, inflow_face, particle_rate } = condition else {
+            continue;
+        };
+        tunnel.accumulated += particle_rate * time.delta_seconds();
+        while tunnel.accumulated >= 1.0 {
+            tunnel.accumulated -= 1.0;
+            let mut position = random_domain_position(rng, Vec3::ZERO, &bounds);
+            let face = inflow_face.index();
+            position[face] = -downstream_sign(inflow_velocity, inflow_face) * bounds.half_extent[face];
+            let perturbation = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * 0.05 * inflow_velocity.length();
+            let particle = Particle { velocity: inflow_velocity + perturbation, mass: fluid.particle_mass(), domain_center: Vec3::ZERO };
+            let momentum = particle.mass * particle.velocity;
+            if pool.acquire(&mut commands, &mut count, Transform::from_translation(position), particle).is_none() {
+                tunnel.accumulated = 0.0;
+                break;
+            }
+            tunnel.inflow_momentum += momentum;
+        }
+    }
+}
+
+fn regulate_density(mut regulator: ResMut<DensityRegulator>, mut query: Query<(Entity, &mut Transform), With<Particle>>, time: Res<Time>,
+mut rng: ResMut<SimRng>, mut since: Local<f32>, bounds: Res<BoundingBox>) {
+    *since += time.delta_seconds();
+    if *since < regulator.regulation_interval {
+        return;
+    }
+    *since = 0.0;
+
+    // coarse grid over the domain
+    const CELLS_PER_AXIS: usize = 5;
+    let half = bounds.half_extent;
+    let cell_size = bounds.size() / CELLS_PER_AXIS as f32;
+    let cell_count = CELLS_PER_AXIS.pow(3);
+    let mut cells: Vec<Vec<Entity>> = vec![Vec::new(); cell_count];
+    for (entity, transform) in query.iter() {
+        let cell = ((transform.translation + half) / cell_size).floor();
+        let cell = cell.clamp(Vec3::ZERO, Vec3::splat((CELLS_PER_AXIS - 1) as f32)).as_uvec3();
+        cells[(cell.z as usize * CELLS_PER_AXIS + cell.y as usize) * CELLS_PER_AXIS + cell.x as usize].push(entity);
+    }
+
+    let counts: Vec<f32> = cells.iter().map(|cell| cell.len() as f32).collect();
+    let mean = counts.iter().sum::<f32>() / cell_count as f32;
+    if mean > 0.0 {
+        let variance = counts.iter().map(|c| (c - mean).powi(2)).sum::<f32>() / cell_count as f32;
+        regulator.cv_sum += variance.sqrt() / mean;
+        regulator.cv_samples += 1;
+    }
+    if !regulator.enabled {
+        return;
+    }
+
+    let target = if regulator.target_density > 0.0 { regulator.target_density * cell_size.x * cell_size.y * cell_size.z } else { mean };
+    let target = target.round().max(0.0) as usize;
+    // surplus in entity order so seeded runs move the same particles
+    let mut surplus: Vec<Entity> = Vec::new();
+    for cell in cells.iter_mut() {
+        cell.sort();
+        while cell.len() > target {
+            surplus.extend(cell.pop());
+        }
+    }
+    let rng = &mut rng.0;
+    for (index, cell) in cells.iter().enumerate() {
+        let corner = Vec3::new((index % CELLS_PER_AXIS) as f32, (index / CELLS_PER_AXIS % CELLS_PER_AXIS) as f32, (index / (CELLS_PER_AXIS * CELLS_PER_AXIS)) as f32) * cell_size - half;
+        for _ in cell.len()..target {
+            let Some(entity) = surplus.pop() else {
+                return;
+            };
+            if let Ok((_, mut transform)) = query.get_mut(entity) {
+                transform.translation = corner + Vec3::new(rng.gen_range(0.0..cell_size.x), rng.gen_range(0.0..cell_size.y), rng.gen_range(0.0..cell_size.z));
+            }
+        }
+    }
+}
+
+fn rebuild_spatial_grid(mut grid: ResMut<SpatialGrid>, query: Query<(Entity, &Transform), With<Particle>>) {
+    grid.clear();
+    for (entity, transform) in query.iter() {
+        grid.insert(entity, transform.translation);
+    }
+}
+
+// Read by blade_collisions to skip particles out of reach of every blade
+fn build_octree(mut octree: ResMut<Octree>, query: Query<(Entity, &Transform), With<Particle>>) {
+    octree.clear();
+    for (entity, transform) in query.iter() {
+        octree.insert(entity, transform.translation);
+    }
+}
+
+// Stays serial: each contact writes to both particles of a pair, so two threads could
+// update the same particle at once. The spatial grid is what keeps it affordable.
+fn compare_particles(mut query: Query<(Entity, &mut Transform, &mut Particle, Option<&mut CollisionCount>)>, grid: Res<SpatialGrid>, time: Res<Time>,
+mut collisions: EventWriter<ParticleParticleCollision>, restitution: Res<Restitution>, mut energy: ResMut<EnergyDiagnostic>,
+mut heat: ResMut<HeatGenerated>, count: Res<ParticleCount>) {
+    let e = restitution.particle_particle;
+    let mut positions: Vec<(Entity, Vec3)> = Vec::with_capacity(count.0);
+    positions.extend(query.iter().map(|(entity, transform, _, _)| (entity, transform.translation)));
+
+    for (entity_a, position_a) in positions {
+        // only neighbouring cells can hold a particle within contact distance
+        for entity_b in grid.query_neighbors(position_a, 2.0 * COLLISION_RADIUS) {
+            // each pair once
+            if entity_b <= entity_a {
+                continue;
+            }
+            let Ok([(_, mut transform_a, mut particle_a, hits_a), (_, mut transform_b, mut particle_b, hits_b)]) = query.get_many_mut([entity_a, entity_b]) else {
+                continue;
+            };
+
+            if distance_between(&transform_a, &transform_b) <= 2.0 * COLLISION_RADIUS {
+                //println!("Collision!");
+                // from B to A at the moment of contact
+                let normal = (transform_a.translation - transform_b.translation).normalize_or_zero();
+                transform_a.translation += -1.0 * particle_a.velocity * time.delta_seconds();
+                transform_b.translation += -1.0 * particle_b.velocity * time.delta_seconds();
+
+                // exchange momentum along the normal only, weighted by mass. With restitution 1
+                // this is the 1D elastic formula v_a' = ((m_a - m_b) v_a + 2 m_b v_b) / (m_a + m_b)
+                let approach = (particle_a.velocity - particle_b.velocity).dot(normal);
+                if approach < 0.0 {
+                    let (m_a, m_b) = (particle_a.mass, particle_b.mass);
+                    let impulse = -(1.0 + e) * approach * m_a * m_b / (m_a + m_b);
+                    let loss = 0.5 * m_a * m_b / (m_a + m_b) * approach * approach * (1.0 - e * e);
+                    energy.collision_loss += loss;
+                    heat.0 += loss;
+                    particle_a.velocity += impulse / m_a * normal;
+                    particle_b.velocity -= impulse / m_b * normal;
+                }
+                for mut hits in [hits_a, hits_b].into_iter().flatten() {
+                    hits.particle_hits += 1;
+                }
+                collisions.send(ParticleParticleCollision { entity_a, entity_b, time: time.elapsed_seconds() });
+            }
+        }
+    }
+}
+
+// Angle of a hub-relative position about +Y in [0, 2 pi), measured from +Z towards +X
+// like the blade's rotation, so a blade at rotation r points along (sin r, 0, cos r)
+fn azimuth_of(rel: Vec3) -> f32 {
+    rel.x.atan2(rel.z).rem_euclid(std::f32::consts::TAU)
+}
+
+fn blade_collisions(blade_query: Query<&PropellerBlade>, mut hub_query: Query<(&mut PropellerHub, &Transform, Option<&TiltAngle>)>,
+mut particle_query: Query<(Entity, &mut Transform, &mut Particle, Option<&mut CollisionCount>), Without<PropellerHub>>, // Mutable
+mut debug_lines: Option<ResMut<DebugLines>>, mut loads: StrikeLoads, mut collisions: EventWriter<BladeParticleCollision>, octree: Res<Octree>,
+model: StrikeModel, wind: Res<WindProfile>, geometry: Res<PropellerGeometry>, mut rng: ResMut<SimRng>,
+config: Res<SimConfig>
+) {
+    let StrikeModel { profile, restitution, cyclic } = model;
+    // particles struck this substep with their impulse magnitude. A particle is struck by one
+    // blade at most, the first to reach it, and respawned once every blade has had its turn.
+    let mut struck: Vec<(Entity, f32)> = Vec::new();
+    for blade in blade_query.iter() {
+        // impulse and torque go back to the hub the blade is mounted on
+        let Ok((mut propeller, hub_transform, tilt)) = hub_query.get_mut(blade.hub) else {
+            continue;
+        };
+        let hub_center = hub_transform.translation + blade.offset;
+        // everything below works in the disk frame, spin about +Y, and goes back out through tilt
+        let tilt = tilt.map_or(Quat::IDENTITY, TiltAngle::rotation);
+        let to_disk = tilt.inverse();
+        let pitch = effective_pitch(cyclic.as_deref(), blade.pitch, propeller.rotation_z + blade.azimuth);
+        for candidate in octree.query_sphere(hub_center, blade.length + COLLISION_RADIUS) {
+            if struck.iter().any(|&(entity, _)| entity == candidate) {
+                continue;
+            }
+            let Ok((particle_entity, part_transform, mut particle, mut hits)) = particle_query.get_mut(candidate) else {
+                continue;
+            };
+            // position relative to the hub, everything below is in the hub's frame
+            let rel = to_disk * (part_transform.translation - hub_center);
+            // Perform comparison and update particles
+            // within the vertical half-extent of the pitched chord at this radius
+            let local_chord = geometry.chord_at(rel.length());
+            if rel[1].abs() < 0.5*local_chord*(pitch.to_radians().sin()) {
+                let temp_transform = Transform::from_translation(hub_center);
+                if distance_between(&part_transform, &temp_transform) < blade.length {
+                    let particle_theta = azimuth_of(rel);
+
+                    let angle_modifier = (local_chord*pitch.to_radians().cos()/(2.0*distance_between(&part_transform, &temp_transform))).atan();
+
+                    // particle angle relative to this blade
+                    // a swept section sits ahead of (or behind) the blade's centreline angle by its
+                    // chordwise shift, projected onto the rotor plane, over the radius
+                    let sweep_lead = (geometry.sweep_offset(rel.length()) * pitch.to_radians().cos() / rel.length()).atan();
+                    let theta = (particle_theta - blade.azimuth.to_radians() - sweep_lead).rem_euclid(std::f32::consts::TAU);
+                    let blade_rotation = propeller.rotation_z + blade.azimuth;
+
+                    // the arc swept this substep, old to new for clockwise and new to old the other way
+                    let direction = propeller.rotation_direction.sign();
+                    let (arc_start, arc_end) = if direction > 0.0 { (propeller.old_rotation_z, propeller.rotation_z) } else { (propeller.rotation_z, propeller.old_rotation_z) };
+                    // measured round from the start of the arc, so an arc running past 360 or below 0 still
+                    // covers the particles on the far side of the wrap
+                    let past_arc_start = (theta - arc_start.to_radians() + angle_modifier).rem_euclid(std::f32::consts::TAU);
+                    if past_arc_start < (arc_end - arc_start).to_radians() + 2.0 * angle_modifier {
+                        let unit_parallel = Vector3::new(blade_rotation.to_radians().sin(),0.0, blade_rotation.to_radians().cos());
+
+                        //unit tilt
+                        let down_value = -(pitch.to_radians().sin()); 
+                        let out_value = (1.0-(down_value*down_value)).sqrt(); //keep unit
+                        let unit_tilt = Vector3::new((blade_rotation - 90.0).to_radians().sin() * out_value, down_value, (blade_rotation - 90.0).to_radians().cos() * out_value);
+                   
+                        let unit_normal = unit_parallel.cross(&unit_tilt);
+
+                        let particle_distance = distance_between(&part_transform, &temp_transform);
+                        let propeller_speed = propeller.angular_v * particle_distance / 360.0;
+                        let propeller_velocity = direction * propeller_speed * Vector3::new((blade_rotation + 90.0).to_radians().sin(), 0.0, (blade_rotation + 90.0).to_radians().cos());
+                        let local_velocity = to_disk * (particle.velocity - wind.wind_at(part_transform.translation));
+                        let net_velocity = Vector3::new(local_velocity.x, local_velocity.y, local_velocity.z) - propeller_velocity;
+
+                        // blade element: flow in the chord plane sets the angle of attack, lift acts
+                        // across it and drag along it. unit_tilt runs along the chord.
+                        let flow = net_velocity - net_velocity.dot(&unit_parallel) * unit_parallel;
+                        let flow_speed = flow.norm();
+                        if flow_speed <= f32::EPSILON {
+                            continue;
+                        }
+                        let flow_dir = flow / flow_speed;
+                        let aoa = flow.dot(&unit_normal).atan2(flow.dot(&unit_tilt)).to_degrees();
+                        let (cl, cd) = profile.lookup_cl_cd(aoa);
+                        let lift_dir = unit_parallel.cross(&flow_dir);
+                        // impulse on the particle, scaled like a dynamic pressure on the particle's own mass
+                        let particle_impulse = 0.5 * particle.mass * flow_speed * propeller.compressibility_factor * restitution.blade_particle * (cl * lift_dir - cd * flow_dir);
+                        let delta_v = tilt * Vec3::new(particle_impulse[0], particle_impulse[1], particle_impulse[2]) / particle.mass;
+                        particle.velocity += delta_v;
+                        // and its reaction on the blade
+                        let mut impulse_vector = -particle_impulse;
+
+                        collisions.send(BladeParticleCollision {
+                            particle_entity,
+                            position: part_transform.translation,
+                            impulse: tilt * Vec3::new(impulse_vector[0], impulse_vector[1], impulse_vector[2]),
+                            blade_rotation_deg: blade_rotation,
+                            pitch_deg: pitch,
+                        });
+
+                        propeller.total_vertical_impulse += impulse_vector[1];
+                        propeller.total_x_impulse += impulse_vector[0];
+                        propeller.total_z_impulse += impulse_vector[2];
+                        propeller.total_world_impulse += tilt * Vec3::new(impulse_vector[0], impulse_vector[1], impulse_vector[2]);
+                        loads.blade_load.add(particle_distance, impulse_vector[1]);
+                        loads.heatmap.add(particle_distance / blade.length);
+                        loads.ripple.frame_impulse += impulse_vector[1];
+                        loads.coupling.mx += impulse_vector[1] * rel[2];
+                        loads.coupling.mz += impulse_vector[1] * rel[0];
+
+                        if let Some(lines) = debug_lines.as_mut() {
+                            lines.0.push((hub_center, hub_center + Vec3::new(impulse_vector[0], impulse_vector[1], impulse_vector[2]), Color::RED, 0.0));
+                        }
+
+                        impulse_vector[1] = 0.0;
+
+                        let moment_arm = Vector3::new(rel[0], 0.0, rel[2]);
+                        let angular_impulse = moment_arm.cross(&impulse_vector);
+
+                        let unit_vertial = Vector3::new(0.0, -1.0, 0.0);
+                        let angular_impulse_mag = angular_impulse.dot(&unit_vertial);
+                        loads.coupling.my += angular_impulse_mag;
+                        propeller.total_reaction_torque += direction * angular_impulse_mag;
+
+                        // drag on either direction of spin slows it down
+                        let delta_angular_v = -direction * angular_impulse_mag / propeller.moi;
+
+                        propeller.angular_v += delta_angular_v;                        
+                    
+                        if let Some(lines) = debug_lines.as_mut() {
+                            lines.0.push((hub_center, hub_center + Vec3::new(moment_arm[0], moment_arm[1], moment_arm[2]), Color::WHITE, 0.0));
+                        }
+
+                        if let Some(hits) = hits.as_mut() {
+                            hits.blade_hits += 1;
+                        }
+                        struck.push((particle_entity, particle_impulse.norm()));
+
+                        //commands.entity(particle_entity).despawn();
+                        //let output = format!("Collision. Propeller angle: {}, particle angle: {}, old propeller angle: {}, vector: {}", blade_rotation.to_string(),theta.to_string(), propeller.old_rotation_z.to_string(), propeller_velocity.to_string());
+                        //("{}", delta_angular_v.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let rng = &mut rng.0;
+    for (entity, magnitude) in struck {
+        if let Ok((_, mut part_transform, mut particle, _)) = particle_query.get_mut(entity) {
+            part_transform.translation = random_domain_position(rng, particle.domain_center, &config.bounding_box);
+            // the struck fluid keeps moving downstream as the rotor's slip-stream
+            particle.velocity.y -= config.slipstream_gain * magnitude / particle.mass;
+        }
+    }
+}
+
+// Flow a blade is evaluated in, omega in rad/s and pitch in degrees
+struct BemConditions {
+    pitch: f32,
+    omega: f32,
+    inflow: f32,
+    density: f32,
+    // blades sharing the annulus
+    blade_count: f32,
+}
+
+// Blade element momentum: each strip's lift and drag from the local flow angle, with the
+// induced velocity found by balancing the strip's thrust against the momentum flux through
+// its annulus, dT = 4 pi r rho (V + vi) vi dr. Returns the blade's (thrust, torque) and
+// hands each strip's radius and thrust to on_element.
+fn blade_element_loads(elements: &mut [BladeElement], length: f32, conditions: &BemConditions, profile: &NacaProfile, mut on_element: impl FnMut(f32, f32)) -> (f32, f32) {
+    let BemConditions { pitch, omega, inflow, density, blade_count } = *conditions;
+    let dr = length / elements.len().max(1) as f32;
+    let mut thrust = 0.0;
+    let mut torque = 0.0;
+    for element in elements.iter_mut() {
+        let tangential = omega.abs() * element.radius;
+        let mut induced = 0.0;
+        let mut element_thrust = 0.0;
+        let mut element_torque = 0.0;
+        for _ in 0..20 {
+            let axial = inflow + induced;
+            let phi = axial.atan2(tangential);
+            let (cl, cd) = profile.lookup_cl_cd(pitch - phi.to_degrees());
+            let dynamic = 0.5 * density * (axial * axial + tangential * tangential) * element.chord * dr;
+            element_thrust = dynamic * (cl * phi.cos() - cd * phi.sin());
+            element_torque = dynamic * (cl * phi.sin() + cd * phi.cos()) * element.radius;
+            element.cl = cl;
+            element.cd = cd;
+            // every blade on the hub loads the same annulus
+            let k = (blade_count * element_thrust / (4.0 * std::f32::consts::PI * element.radius * density * dr)).max(0.0);
+            let next = 0.5 * (-inflow + (inflow * inflow + 4.0 * k).sqrt());
+            // under-relaxed, the plain fixed point oscillates at high pitch
+            induced = 0.5 * (induced + next);
+        }
+        element.pitch = pitch;
+        on_element(element.radius, element_thrust);
+        thrust += element_thrust;
+        torque += element_torque;
+    }
+    (thrust, torque)
+}
+
+fn blades_per_hub<'a>(blades: impl Iterator<Item = &'a PropellerBlade>) -> HashMap<Entity, f32> {
+    let mut counts: HashMap<Entity, f32> = HashMap::new();
+    for blade in blades {
+        *counts.entry(blade.hub).or_default() += 1.0;
+    }
+    counts
+}
+
+fn blade_element_forces(mut blade_query: Query<&mut PropellerBlade>, mut hub_query: Query<&mut PropellerHub>, substep: Res<SubstepTime>,
+profile: Res<NacaProfile>, fluid: Res<FluidDensity>, wind: Res<WindProfile>, cyclic: Option<Res<CyclicPitch>>,
+mut blade_load: ResMut<BladeLoadDistribution>, mut ripple: ResMut<PropellerThrustRipple>) {
+    let blade_counts = blades_per_hub(blade_query.iter());
+    for mut blade in blade_query.iter_mut() {
+        let Ok(mut hub) = hub_query.get_mut(blade.hub) else {
+            continue;
+        };
+        let conditions = BemConditions {
+            pitch: effective_pitch(cyclic.as_deref(), blade.pitch, hub.rotation_z + blade.azimuth),
+            omega: hub.angular_v.to_radians(),
+            inflow: wind.inflow_speed(),
+            density: fluid.density_kg_per_m3,
+            blade_count: blade_counts[&blade.hub],
+        };
+        let length = blade.length;
+        let (thrust, torque) = blade_element_loads(&mut blade.elements, length, &conditions, &profile, |radius, element_thrust| {
+            blade_load.add(radius, element_thrust * substep.dt);
+        });
+        hub.total_vertical_impulse += thrust * substep.dt;
+        hub.total_reaction_torque += torque * substep.dt;
+        ripple.frame_impulse += thrust * substep.dt;
+        // drag torque opposes the spin, angular_v is kept in deg/s
+        let moi = hub.moi;
+        hub.angular_v -= hub.angular_v.signum() * (torque * substep.dt / moi).to_degrees();
+    }
+}
+
+fn check_energy_conservation(particles: Query<(&Transform, &Particle)>, hubs: Query<&PropellerHub>, blades: Query<&PropellerBlade>,
+governor_state: Res<GovernorState>, mut energy: ResMut<EnergyDiagnostic>, gravity: Res<Gravity>, wind: Res<WindProfile>,
+fluid: Res<FluidDensity>, profile: Res<NacaProfile>, config: Res<SimConfig>, time: Res<Time>, solver: Res<SolverMode>,
+mode: Res<PhysicsMode>, mut temperature: ResMut<FluidTemperature>, dissipation: Res<ViscousDissipation>) {
+    let dt = time.delta_seconds();
+    let particle_ke: f32 = particles.iter().map(|(_, p)| 0.5 * p.mass * p.velocity.length_squared()).sum();
+    // gravity and wind act as body forces on every particle
+    let body_work: f32 = particles.iter().map(|(t, p)| p.mass * (gravity.acceleration_at(t.translation) + wind.wind_at(t.translation)).dot(p.velocity) * dt).sum();
+    // angular_v is in deg/s, the energy needs rad/s
+    let rotor_ke: f32 = hubs.iter().map(|hub| 0.5 * hub.moi * hub.angular_v.to_radians().powi(2)).sum();
+    let particle_count = particles.iter().count();
+
+    // governor work resets with every trial, as do the rotors, so the budget starts over
+    let new_trial = governor_state.work < energy.work_input;
+    let restarted = new_trial || particle_count != energy.particle_count;
+    if new_trial && energy.initialized {
+        info!("Energy drift over the trial with {:?}: {} J, fluid at {} K after {} J of heating", *solver, energy.trial_drift, temperature.0, energy.heat);
+        energy.trial_drift = 0.0;
+    }
+    if energy.initialized && !restarted && dt > 0.0 {
+        let work = governor_state.work - energy.work_input;
+        let change = (particle_ke - energy.particle_ke) + (rotor_ke - energy.rotor_ke);
+        let supplied = work + body_work - energy.collision_loss;
+        let total = (particle_ke + rotor_ke).max(f32::EPSILON);
+        energy.trial_drift += change - supplied;
+
+        let rotors = hubs.iter().count().max(1) as f32;
+        let n = hubs.iter().map(|hub| hub.angular_v / 360.0).sum::<f32>() / rotors;
+        let density = fluid.density_kg_per_m3;
+        let power_coefficient = |power: f32| PropellerCoefficients::compute(0.0, power, n, config.bounding_box.size().x, density, wind.inflow_speed()).cp;
+        energy.power_coefficient = power_coefficient(work / dt / rotors);
+        // only meaningful while the particles carry the loads, in BladeElement mode the shaft work is the BEM power
+        energy.bem_power_coefficient = (energy.bem_comparison && *mode == PhysicsMode::MolecularDynamics).then(|| {
+            let blade_counts = blades_per_hub(blades.iter());
+            let bem_power: f32 = blades.iter().filter_map(|blade| {
+                let hub = hubs.get(blade.hub).ok()?;
+                let conditions = BemConditions { pitch: blade.pitch, omega: hub.angular_v.to_radians(), inflow: wind.inflow_speed(), density, blade_count: blade_counts[&blade.hub] };
+                let (_, torque) = blade_element_loads(&mut blade.elements.clone(), blade.length, &conditions, &profile, |_, _| {});
+                Some(torque * conditions.omega.abs())
+            }).sum();
+            power_coefficient(bem_power / rotors)
+        });
+
+        let elapsed = time.elapsed_seconds();
+        if (change - supplied).abs() > energy.tolerance * total && elapsed - energy.last_warning >= 1.0 {
+            energy.last_warning = elapsed;
+            let bem = energy.bem_power_coefficient.map_or(String::new(), |cp| format!(", blade element CP {}", cp));
+            warn!("Energy budget off by {} J in one step: kinetic energy changed by {} J, work in less losses was {} J (CP {}{})",
+                change - supplied, change, supplied, energy.power_coefficient, bem);
+        }
+    }
+    // what collisions and drag dissipated warms the whole fluid
+    let heat = energy.collision_loss + dissipation.power * dt;
+    energy.heat += heat;
+    let fluid_mass: f32 = particles.iter().map(|(_, p)| p.mass).sum();
+    if fluid_mass > 0.0 {
+        temperature.0 += heat / (fluid_mass * fluid.specific_heat);
+    }
+    energy.particle_ke = particle_ke;
+    energy.rotor_ke = rotor_ke;
+    energy.work_input = governor_state.work;
+    energy.collision_loss = 0.0;
+    energy.particle_count = particle_count;
+    energy.initialized = true;
+}
+
+fn vtk_export_system(config: Res<VtkExportConfig>, particles: Query<(Entity, &Transform, &Particle)>, blades: Query<&Transform, With<PropellerBlade>>,
+geometry: Res<PropellerGeometry>, time: Res<Time>, mut since_export: Local<Option<f32>>, mut index: Local<u32>, logger: Res<DataLogger>) {
+    if !config.enabled {
+        return;
+    }
+    // the first step exports straight away
+    let elapsed = since_export.map_or(config.interval_seconds, |t| t + time.delta_seconds());
+    if elapsed < config.interval_seconds {
+        *since_export = Some(elapsed);
+        return;
+    }
+    *since_export = Some(0.0);
+
+    let particle_data: Vec<(u32, Vec3, Vec3)> = particles.iter().map(|(entity, transform, particle)| (entity.index(), transform.translation, particle.velocity)).collect();
+    let corners = geometry.planform_corners();
+    let blade_quads: Vec<[Vec3; 4]> = blades.iter().map(|transform| corners.map(|corner| transform.transform_point(corner))).collect();
+
+    let (vtk_dir, file_name) = (config.output_dir.clone(), format!("particles_{:05}.vtu", *index));
+    logger.write_file("VTK snapshot", move |dir| {
+        let vtk_dir = dir.join(vtk_dir);
+        std::fs::create_dir_all(&vtk_dir)?;
+        write_vtu(&vtk_dir.join(file_name), &particle_data, &blade_quads)
+    });
+    *index += 1;
+}
+
+fn update_slipstream(mut field: ResMut<SlipStreamField>, query: Query<(&Transform, &Particle)>) {
+    let mut sums = vec![Vec3::ZERO; field.cells.len()];
+    let mut counts = vec![0u32; field.cells.len()];
+    // every batch domain folds onto the one field around the origin
+    for (transform, particle) in query.iter() {
+        if let Some(index) = field.index_of(transform.translation - particle.domain_center) {
+            sums[index] += particle.velocity;
+            counts[index] += 1;
+        }
+    }
+    for ((cell, sum), count) in field.cells.iter_mut().zip(sums).zip(counts) {
+        *cell = if count > 0 { sum / count as f32 } else { Vec3::ZERO };
+    }
+}
+
+fn update_velocity_histogram(mut histogram: ResMut<VelocityHistogram>, query: Query<&Particle>) {
+    histogram.steps += 1;
+    if !histogram.steps.is_multiple_of(histogram.sample_every.max(1)) {
+        return;
+    }
+    for particle in query.iter() {
+        histogram.add(particle.velocity.length());
+    }
+}
+
+fn compute_pressure_field(mut field: ResMut<PressureField>, query: Query<(&Transform, &Particle)>) {
+    for (transform, particle) in query.iter() {
+        field.add_sample(transform.translation, particle.velocity.length_squared());
+    }
+}
+
+fn record_thrust_ripple(mut ripple: ResMut<PropellerThrustRipple>, mut history: ResMut<ThrustHistory>, hub_query: Query<&PropellerHub>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    if dt > 0.0 {
+        let thrust = ripple.frame_impulse / dt;
+        ripple.samples.push((dt, thrust));
+        let tip_mach = hub_query.iter().map(|hub| hub.tip_mach).fold(0.0, f32::max);
+        history.push(dt, thrust, tip_mach);
+    }
+    ripple.frame_impulse = 0.0;
+}
+
+fn record_startup_transient(hub_query: Query<(Entity, &PropellerHub)>, blade_query: Query<&PropellerBlade>, mut transient: ResMut<PropellerStartupTransient>, time: Res<Time>,
+logger: Res<DataLogger>) {
+    if !transient.recording {
+        return;
+    }
+    transient.elapsed += time.delta_seconds();
+    // rotors of an array start together, the first one stands for them all
+    if let Some((hub_entity, prop)) = hub_query.iter().next() {
+        let sample = (transient.elapsed, prop.angular_v);
+        transient.samples.push(sample);
+        if transient.is_steady() {
+            transient.finish(hub_pitch(hub_entity, blade_query.iter()).unwrap_or_default(), &logger);
+        }
+    }
+}
+
+// Tip Mach number of each rotor from its spin rate, and the Prandtl-Glauert factor
+// 1 / sqrt(1 - M^2) blade_collisions scales strikes by. Held at its M = 0.7 value beyond that.
+fn update_compressibility(mut hub_query: Query<&mut PropellerHub>, geometry: Res<PropellerGeometry>, speed_of_sound: Res<SpeedOfSound>,
+scale: Res<SimulationScale>, mut warned: Local<bool>) {
+    const MACH_LIMIT: f32 = 0.7;
+    for mut hub in hub_query.iter_mut() {
+        let tip_speed = hub.angular_v.to_radians().abs() * geometry.span * scale.length_m_per_unit;
+        hub.tip_mach = tip_speed / speed_of_sound.0;
+        if hub.tip_mach > MACH_LIMIT && !*warned {
+            warn!("Tip Mach number {} is transonic, the Prandtl-Glauert correction is capped at M = {}", hub.tip_mach, MACH_LIMIT);
+            *warned = true;
+        }
+        let mach = hub.tip_mach.min(MACH_LIMIT);
+        hub.compressibility_factor = 1.0 / (1.0 - mach * mach).sqrt();
+    }
+}
+
+// Translation and rotation of a blade at rotation_deg around the hub
+fn blade_transform(rotation_deg: f32, pitch: f32, length: f32) -> (Vec3, Quat) {
+    let rotation_z = Quat::from_rotation_y(rotation_deg.to_radians() + std::f32::consts::FRAC_PI_2);
+    let rotation_pitch = Quat::from_rotation_x((90.0 - pitch).to_radians());
+
+    // Rotate around cube center by first moving it to (0,0,0), rotating, and moving back
+    let pivot = Vec3::new(-length / 2.0, 0.0, 0.0); // Move back by half its length before rotating
+    (rotation_z * pivot + Vec3::ZERO, rotation_z * rotation_pitch)
+}
+
+// Rotates the propeller and checks blade collisions substeps times per physics step, so a fast
+// blade sweeps a short arc each pass instead of tunnelling past particles. A struck particle
+// is respawned at once, so it is hit at most once per step and its impulse is not rescaled.
+fn run_propeller_substeps(world: &mut World) {
+    let substeps = world.resource::<SubstepCount>().0.max(1);
+    let dt = world.resource::<Time>().delta_seconds() / substeps as f32;
+    world.insert_resource(SubstepTime { dt });
+    for _ in 0..substeps {
+        world.run_schedule(PropellerSubstep);
+    }
+}
+
+fn update_rectangle_rotation(mut hub_query: Query<(&mut PropellerHub, &mut Transform, Option<&TiltAngle>, &mut HubGovernor)>, mut blade_query: Query<(&PropellerBlade, &mut Transform), Without<PropellerHub>>, substep: Res<SubstepTime>,
+governor: Res<RotorGovernor>, mut governor_state: ResMut<GovernorState>, cyclic: Option<Res<CyclicPitch>>, compliance: Res<MountCompliance>,
+startup: Res<PropellerStartup>, phase: Res<SimulationPhase>) {
+    let power_fraction = startup.power_fraction(&phase);
+    for (mut rect, mut hub_transform, tilt, mut hub_governor) in hub_query.iter_mut() {
+        if rect.rotation_z >= 360.0 {
+            rect.rotation_z -= 360.0;
+        }
+        else if rect.rotation_z < 0.0 {
+            rect.rotation_z += 360.0;
+        }
+        let moi = rect.moi;
+        match *governor {
+            RotorGovernor::ConstantPower(power) => {
+                let power = power * power_fraction;
+                // constant power, integrated through the rotational energy so the propeller can start from rest
+                let energy = rect.angular_v * rect.angular_v.abs() + 2.0 * power * substep.dt / moi;
+                rect.angular_v = energy.signum() * energy.abs().sqrt();
+                governor_state.work += power * substep.dt;
+                hub_governor.work += power * substep.dt;
+            }
+            RotorGovernor::ConstantRPM { target_rpm, kp, ki, kd } => {
+                let error = target_rpm * hub_governor.rpm_ratio - rect.angular_v * 60.0 / 360.0;
+                hub_governor.integral += error * substep.dt;
+                let derivative = hub_governor.previous_error.map_or(0.0, |previous| (error - previous) / substep.dt);
+                hub_governor.previous_error = Some(error);
+                let torque = kp * error + ki * hub_governor.integral + kd * derivative;
+                // torque in N m gives rad/s^2, angular_v is kept in deg/s
+                rect.angular_v += (torque * substep.dt / moi).to_degrees();
+                governor_state.work += torque * rect.angular_v.to_radians() * substep.dt;
+                hub_governor.work += torque * rect.angular_v.to_radians() * substep.dt;
+            }
+        }
+        //println!("{}", rect.angular_v.to_string());
+        //println!("{}", rect.rotation_z.to_string());
+        rect.old_rotation_z = rect.rotation_z;
+        rect.rotation_z += rect.rotation_direction.sign() * rect.angular_v * substep.dt;
+        rect.peak_angular_v = rect.peak_angular_v.max(rect.angular_v);
+        rect.min_angular_v = rect.min_angular_v.min(rect.angular_v);
+
+        // the imbalance pulls the hub towards its own azimuth, the first blade's
+        let theta = rect.rotation_z.to_radians();
+        let force = rect.imbalance_mass * rect.imbalance_radius * rect.angular_v.to_radians().powi(2);
+        let tilt = tilt.map_or(Quat::IDENTITY, TiltAngle::rotation);
+        let offset = tilt * (force * compliance.0 * Vec3::new(theta.sin(), 0.0, theta.cos()));
+        hub_transform.translation += offset - rect.vibration_offset;
+        rect.vibration_offset = offset;
+        rect.peak_vibration = rect.peak_vibration.max(offset.length());
+    }
+
+    for (blade, mut transform) in blade_query.iter_mut() {
+        if let Ok((hub, hub_transform, tilt, _)) = hub_query.get(blade.hub) {
+            let blade_rotation = hub.rotation_z + blade.azimuth;
+            let pitch = effective_pitch(cyclic.as_deref(), blade.pitch, blade_rotation);
+            let (translation, rotation) = blade_transform(blade_rotation, pitch, blade.length);
+            let tilt = tilt.map_or(Quat::IDENTITY, TiltAngle::rotation);
+            transform.translation = hub_transform.translation + blade.offset + tilt * translation;
+            transform.rotation = tilt * rotation;
+        }
+    }
+}
+
+fn toggle_manual_control(keys: Res<Input<KeyCode>>, mut manual: ResMut<ManualControl>, mut measurement: ResMut<ManualMeasurement>,
+mut blade_query: Query<(Entity, &mut PropellerBlade)>, mut phase: ResMut<SimulationPhase>) {
+    if !keys.just_pressed(KeyCode::M) {
+        return;
+    }
+    manual.0 = !manual.0;
+    if manual.0 {
+        measurement.sweep_pitches = blade_query.iter().map(|(entity, blade)| (entity, blade.pitch)).collect();
+    } else {
+        // the manual measurement cleared the trial's impulse, so the sweep starts the trial over
+        for (entity, pitch) in measurement.sweep_pitches.drain(..) {
+            if let Ok((_, mut blade)) = blade_query.get_mut(entity) {
+                blade.pitch = pitch;
+            }
+        }
+        *phase = SimulationPhase::Warmup { steps: 0 };
+    }
+}
+
+// Up and Down step every blade by a degree and restart the measurement, Return appends
+// (pitch, impulse, thrust rate) to MANUAL_CSV_PATH
+fn manual_pitch_control(keys: Res<Input<KeyCode>>, manual: Res<ManualControl>, mut measurement: ResMut<ManualMeasurement>,
+mut hub_query: Query<(Entity, &mut PropellerHub)>, mut blade_query: Query<&mut PropellerBlade>, csv_output: Res<CsvOutputConfig>, time: Res<Time>,
+logger: Res<DataLogger>) {
+    if !manual.0 {
+        return;
+    }
+    measurement.elapsed += time.delta_seconds();
+    let step = match (keys.just_pressed(KeyCode::Up), keys.just_pressed(KeyCode::Down)) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    };
+    if step != 0.0 || manual.is_changed() {
+        for mut blade in blade_query.iter_mut() {
+            blade.pitch = (blade.pitch + step).clamp(0.0, 90.0);
+        }
+        for (_, mut hub) in hub_query.iter_mut() {
+            hub.total_vertical_impulse = 0.0;
+        }
+        measurement.elapsed = 0.0;
+    }
+    if keys.just_pressed(KeyCode::Return) && measurement.elapsed > 0.0 {
+        let Some((hub_entity, hub)) = hub_query.iter().next() else {
+            return;
+        };
+        let pitch = hub_pitch(hub_entity, blade_query.iter()).unwrap_or_default();
+        let csv_output = csv_output.clone();
+        let row = [pitch, hub.total_vertical_impulse, hub.total_vertical_impulse / measurement.elapsed];
+        let header = ["pitch_deg", "impulse", "thrust_rate"].map(String::from);
+        logger.write_file("manual measurement", move |dir| {
+            let output = CsvOutputConfig { file_path: dir.join(MANUAL_CSV_PATH).to_string_lossy().into_owned(), ..csv_output };
+            append_to_csv(&output, &header, &row)
+        });
+    }
+}
+
+fn toggle_velocity_vectors(keys: Res<Input<KeyCode>>, mut show: ResMut<ShowVelocityVectors>) {
+    if keys.just_pressed(KeyCode::V) {
+        show.0 = !show.0;
+    }
+}
+
+fn handle_cross_section_input(keys: Res<Input<KeyCode>>, mut view: ResMut<CrossSectionView>) {
+    if keys.just_pressed(KeyCode::C) {
+        view.enabled = !view.enabled;
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        view.plane_d -= CrossSectionView::SCROLL_STEP;
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        view.plane_d += CrossSectionView::SCROLL_STEP;
+    }
+}
+
+// Particles in the slice as circles growing and reddening with speed, with their velocity
+// arrows, and each blade's section where its span crosses the plane
+fn render_cross_section(mut gizmos: Gizmos, view: Res<CrossSectionView>, particle_query: Query<(&Transform, &Particle)>,
+blade_query: Query<(&PropellerBlade, &Transform), Without<Particle>>, hub_query: Query<(&PropellerHub, &Transform, Option<&TiltAngle>), Without<Particle>>,
+geometry: Res<PropellerGeometry>, max_speed: Res<MaxParticleSpeed>, settings: Res<VelocityVectorSettings>) {
+    if !view.enabled {
+        return;
+    }
+    let normal = view.plane_normal.normalize();
+    for (transform, particle) in particle_query.iter() {
+        let position = transform.translation;
+        if (position.dot(normal) - view.plane_d).abs() > CrossSectionView::THICKNESS {
+            continue;
+        }
+        let t = (particle.velocity.length() / max_speed.0).min(1.0);
+        gizmos.circle(position, normal, COLLISION_RADIUS * (0.5 + t), Color::rgb(t, 0.2, 1.0 - t));
+        gizmos.line(position, position + particle.velocity * settings.scale, Color::GREEN);
+    }
+
+    for (blade, blade_transform) in blade_query.iter() {
+        let Ok((hub, hub_transform, tilt)) = hub_query.get(blade.hub) else {
+            continue;
+        };
+        let rotation = (hub.rotation_z + blade.azimuth).to_radians();
+        let tilt = tilt.map_or(Quat::IDENTITY, TiltAngle::rotation);
+        let direction = tilt * Vec3::new(rotation.sin(), 0.0, rotation.cos());
+        let root = hub_transform.translation + blade.offset;
+        let along = direction.dot(normal);
+        if along.abs() < f32::EPSILON {
+            continue;
+        }
+        let radius = (view.plane_d - root.dot(normal)) / along;
+        if !(0.0..=blade.length).contains(&radius) {
+            continue;
+        }
+        // the planform lies in the blade's local XY plane, chord along Y
+        let center = root + direction * radius;
+        let chord = blade_transform.rotation * Vec3::Y * geometry.chord_at(radius) * 0.5;
+        let thickness = blade_transform.rotation * Vec3::Z * geometry.thickness * 0.5;
+        let corners = [center - chord - thickness, center + chord - thickness, center + chord + thickness, center - chord + thickness];
+        for i in 0..4 {
+            gizmos.line(corners[i], corners[(i + 1) % 4], Color::WHITE);
+        }
+    }
+}
+
+fn toggle_propeller_disk(keys: Res<Input<KeyCode>>, mut show: ResMut<ShowPropellerDisk>) {
+    if keys.just_pressed(KeyCode::D) {
+        show.0 = !show.0;
+    }
+}
+
+fn update_propeller_disk_visibility(show_disk: Res<ShowPropellerDisk>, show_vectors: Res<ShowVelocityVectors>, mut query: Query<&mut Visibility, With<PropellerDiskMarker>>) {
+    let visibility = if show_disk.0 && show_vectors.0 { Visibility::Visible } else { Visibility::Hidden };
+    for mut disk in query.iter_mut() {
+        *disk = visibility;
+    }
+}
+
+// Each blade's span in num_bins sections coloured by the strikes the BladeHeatmap holds there
+fn draw_blade_heatmap(mut gizmos: Gizmos, heatmap: Res<BladeHeatmap>, blade_query: Query<&PropellerBlade>, hub_query: Query<(&PropellerHub, &Transform)>) {
+    for blade in blade_query.iter() {
+        let Ok((hub, hub_transform)) = hub_query.get(blade.hub) else {
+            continue;
+        };
+        let rotation = (hub.rotation_z + blade.azimuth).to_radians();
+        let direction = Vec3::new(rotation.sin(), 0.0, rotation.cos());
+        let root = hub_transform.translation + blade.offset;
+        let section = blade.length / heatmap.num_bins as f32;
+        for bin in 0..heatmap.num_bins {
+            let start = root + direction * section * bin as f32;
+            gizmos.line(start, start + direction * section, heatmap.color(bin));
+        }
+    }
+}
+
+// Root, mid and tip thirds of the disk, where blade strikes land in span
+fn draw_disk_zones(mut gizmos: Gizmos, show_disk: Res<ShowPropellerDisk>, show_vectors: Res<ShowVelocityVectors>, geometry: Res<PropellerGeometry>, array: Res<PropellerArray>) {
+    if !(show_disk.0 && show_vectors.0) {
+        return;
+    }
+    let zones = [(1.0 / 3.0, Color::GREEN), (2.0 / 3.0, Color::YELLOW), (1.0, Color::ORANGE_RED)];
+    for center in array.hub_positions() {
+        for (fraction, color) in zones {
+            gizmos.circle(center, Vec3::Y, fraction * geometry.span, color);
+        }
+        for i in 0..8 {
+            let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+            gizmos.line(center, center + geometry.span * Vec3::new(angle.sin(), 0.0, angle.cos()), Color::GRAY);
+        }
+    }
+}
+
+// A sphere per polar cell, drawn around the first rotor and sized by its share of the densest cell
+fn draw_polar_density(mut gizmos: Gizmos, show_disk: Res<ShowPropellerDisk>, show_vectors: Res<ShowVelocityVectors>, field: Res<PolarDensityField>,
+hub_query: Query<(&Transform, Option<&TiltAngle>), With<PropellerHub>>, geometry: Res<PropellerGeometry>) {
+    if !(show_disk.0 && show_vectors.0) {
+        return;
+    }
+    let Some((hub_transform, tilt)) = hub_query.iter().next() else {
+        return;
+    };
+    let to_world = tilt.map_or(Quat::IDENTITY, |t| t.rotation());
+    let max = field.data.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return;
+    }
+    let largest = 0.5 * geometry.span / field.ring_bins as f32;
+    for ring in 0..field.ring_bins {
+        for sector in 0..field.angle_bins {
+            let t = field.data[ring * field.angle_bins + sector] / max;
+            if t <= 0.0 {
+                continue;
+            }
+            let (radius, theta) = field.cell_center(ring, sector, geometry.span);
+            let position = hub_transform.translation + to_world * (radius * Vec3::new(theta.sin(), 0.0, theta.cos()));
+            gizmos.sphere(position, Quat::IDENTITY, largest * t, Color::rgb(t, 0.2, 1.0 - t));
+        }
+    }
+}
+
+fn draw_velocity_vectors(mut gizmos: Gizmos, show: Res<ShowVelocityVectors>, settings: Res<VelocityVectorSettings>, query: Query<(&Transform, &Particle)>) {
+    if !show.0 {
+        return;
+    }
+    let mut nearby: Vec<(f32, Vec3, Vec3)> = query
+        .iter()
+        .map(|(transform, particle)| (transform.translation.length(), transform.translation, particle.velocity))
+        .filter(|&(distance, _, _)| distance <= settings.max_distance)
+        .collect();
+    if nearby.len() > settings.max_arrows {
+        nearby.sort_by(|a, b| a.0.total_cmp(&b.0));
+        nearby.truncate(settings.max_arrows);
+    }
+    for (_, position, velocity) in nearby {
+        gizmos.line(position, position + velocity * settings.scale, Color::GREEN);
+    }
+}
+
+fn visualize_slipstream(mut gizmos: Gizmos, field: Res<SlipStreamField>, settings: Res<VelocityVectorSettings>) {
+    for (index, velocity) in field.cells.iter().enumerate() {
+        if *velocity == Vec3::ZERO {
+            continue;
+        }
+        let center = field.cell_center(index);
+        gizmos.line(center, center + *velocity * settings.scale, Color::CYAN);
+    }
+}
+
+// Blue for the lowest pressure (fastest flow) through red for the highest
+fn visualize_pressure_field(mut gizmos: Gizmos, field: Res<PressureField>, fluid: Res<FluidDensity>) {
+    let pressures: Vec<(IVec3, f32)> = field.cells.iter().map(|(pos, cell)| (*pos, PressureField::pressure(cell, fluid.density_kg_per_m3))).collect();
+    let low = pressures.iter().map(|&(_, p)| p).fold(f32::INFINITY, f32::min);
+    let high = pressures.iter().map(|&(_, p)| p).fold(f32::NEG_INFINITY, f32::max);
+    let range = (high - low).max(f32::EPSILON);
+    for (pos, p) in pressures {
+        let t = (p - low) / range;
+        let center = (pos.as_vec3() + Vec3::splat(0.5)) * field.cell_size;
+        gizmos.cuboid(Transform::from_translation(center).with_scale(Vec3::splat(field.cell_size)), Color::rgb(t, 0.0, 1.0 - t));
+    }
+}
+
+// total_vertical_impulse of every rotor over the time collected so far; nothing while warming up
+fn record_thrust_graph(mut graph: ResMut<ThrustGraph>, hub_query: Query<&PropellerHub>, state: Res<SimulationState>) {
+    if state.time_elapsed <= 0.0 {
+        return;
+    }
+    let impulse: f32 = hub_query.iter().map(|hub| hub.total_vertical_impulse).sum();
+    graph.history.push_back(impulse / state.time_elapsed);
+    while graph.history.len() > graph.max_samples {
+        graph.history.pop_front();
+    }
+}
+
+// Outline, then the history stretched between its current min and max
+fn draw_thrust_graph(mut gizmos: Gizmos, graph: Res<ThrustGraph>) {
+    let origin = graph.display_position;
+    let size = graph.display_size;
+    let corners = [origin, origin + Vec3::X * size.x, origin + Vec3::new(size.x, size.y, 0.0), origin + Vec3::Y * size.y];
+    for i in 0..4 {
+        gizmos.line(corners[i], corners[(i + 1) % 4], Color::WHITE);
+    }
+    if graph.history.len() < 2 {
+        return;
+    }
+    let min = graph.history.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = graph.history.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+    let step = size.x / (graph.max_samples - 1).max(1) as f32;
+    let point = |i: usize, value: f32| origin + Vec3::new(i as f32 * step, (value - min) / range * size.y, 0.0);
+    for (i, pair) in graph.history.iter().zip(graph.history.iter().skip(1)).enumerate() {
+        gizmos.line(point(i, *pair.0), point(i + 1, *pair.1), Color::YELLOW);
+    }
+}
+
+fn draw_boundary_cube(mut gizmos: Gizmos, config: Res<SimConfig>, bounds: Res<BoundingBox>) {
+    for center in config.domain_centers() {
+        draw_domain_cube(&mut gizmos, center, bounds.half_extent);
+    }
+}
+
+fn draw_domain_cube(gizmos: &mut Gizmos, center: Vec3, half: Vec3) {
+    let corners = [
+        Vec3::new(-half.x, -half.y, -half.z),
+        Vec3::new(half.x, -half.y, -half.z),
+        Vec3::new(half.x, half.y, -half.z),
+        Vec3::new(-half.x, half.y, -half.z),
+        Vec3::new(-half.x, -half.y, half.z),
+        Vec3::new(half.x, -half.y, half.z),
+        Vec3::new(half.x, half.y, half.z),
+        Vec3::new(-half.x, half.y, half.z),
+    ];
+
+    let edges = [
+        (0, 1), (1, 2), (2, 3), (3, 0), // Bottom square
+        (4, 5), (5, 6), (6, 7), (7, 4), // Top square
+        (0, 4), (1, 5), (2, 6), (3, 7), // Vertical edges
+    ];
+
+    for &(start, end) in &edges {
+        gizmos.line(center + corners[start], center + corners[end], Color::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::SeedableRng;
+
+    // A two-bladed rotor at the origin at 10 degrees pitch and one particle at rest at position,
+    // with everything blade_collisions reads
+    fn strike_world(density: f32, geometry: PropellerGeometry, position: Vec3) -> World {
+        let fluid = FluidDensity { density_kg_per_m3: density, ..FluidDensity::AIR_SEA_LEVEL };
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.insert_resource(BladeLoadDistribution::new(4.0, 8));
+        world.init_resource::<PropellerThrustRipple>();
+        world.init_resource::<ThrustMomentCoupling>();
+        world.insert_resource(BladeHeatmap::new(10));
+        world.init_resource::<Events<BladeParticleCollision>>();
+        world.init_resource::<NacaProfile>();
+        world.insert_resource(Restitution { particle_particle: 1.0, blade_particle: 1.0 });
+        world.init_resource::<WindProfile>();
+        world.insert_resource(geometry);
+        world.insert_resource(SimRng(rand::rngs::StdRng::seed_from_u64(1)));
+        world.init_resource::<SimConfig>();
+        let hub = world.spawn((Transform::IDENTITY, PropellerHub { rotation_z: 0.0, old_rotation_z: 0.0, angular_v: 3600.0, mass: 5.0, moi: 1.0,
+            total_vertical_impulse: 0.0, total_x_impulse: 0.0, total_z_impulse: 0.0, total_reaction_torque: 0.0, peak_angular_v: 3600.0, min_angular_v: 3600.0,
+            rotation_direction: RotationDirection::default(), hub_geometry: HubGeometry::default(), tip_mach: 0.0, compressibility_factor: 1.0, total_world_impulse: Vec3::ZERO,
+            imbalance_mass: 0.0, imbalance_radius: 0.0, vibration_offset: Vec3::ZERO, peak_vibration: 0.0 })).id();
+        for azimuth in [0.0, 180.0] {
+            world.spawn(PropellerBlade { hub, pitch: 10.0, azimuth, offset: Vec3::ZERO, length: geometry.span, elements: Vec::new() });
+        }
+        let particle = world.spawn((Transform::from_translation(position), Particle { velocity: Vec3::ZERO, mass: fluid.particle_mass(), domain_center: Vec3::splat(100.0) })).id();
+        let mut octree = Octree::new(Vec3::ZERO, 5.0);
+        octree.insert(particle, position);
+        world.insert_resource(octree);
+        world
+    }
+
+    // turns the rotor from one angle to the next in degrees and runs one collision pass,
+    // giving the number of strikes. A struck particle respawns inside the box, where a later
+    // pass could hit it again, so it is parked out of the blades' reach.
+    fn sweep(world: &mut World, from: f32, to: f32) -> usize {
+        for mut hub in world.query::<&mut PropellerHub>().iter_mut(world) {
+            hub.old_rotation_z = from;
+            hub.rotation_z = to;
+        }
+        let before: Vec<(Entity, Vec3)> = world.query_filtered::<(Entity, &Transform), With<Particle>>().iter(world).map(|(entity, transform)| (entity, transform.translation)).collect();
+        world.run_system_once(blade_collisions);
+        let mut strikes = 0;
+        for (entity, position) in before {
+            let mut transform = world.get_mut::<Transform>(entity).unwrap();
+            if transform.translation != position {
+                transform.translation = Vec3::splat(100.0);
+                strikes += 1;
+            }
+        }
+        strikes
+    }
+
+    fn vertical_impulse(world: &mut World) -> f32 {
+        world.query::<&PropellerHub>().iter(world).map(|hub| hub.total_vertical_impulse).sum()
+    }
+
+    // at radius along the blade at rotation angle degrees, in the disk plane
+    fn disk_position(radius: f32, angle: f32) -> Vec3 {
+        Vec3::new(angle.to_radians().sin(), 0.0, angle.to_radians().cos()) * radius
+    }
+
+    #[test]
+    fn water_strike_outweighs_air_by_the_density_ratio() {
+        let position = disk_position(2.0, 10.0);
+        let mut air = strike_world(FluidDensity::AIR_SEA_LEVEL.density_kg_per_m3, PropellerGeometry::default(), position);
+        let mut water = strike_world(1000.0, PropellerGeometry::default(), position);
+        assert_eq!(sweep(&mut air, 5.0, 15.0), 1);
+        assert_eq!(sweep(&mut water, 5.0, 15.0), 1);
+        let ratio = vertical_impulse(&mut water) / vertical_impulse(&mut air);
+        assert!((ratio - 1000.0 / 1.225).abs() < 1.0, "water / air impulse {}", ratio);
+    }
+
+    #[test]
+    fn substeps_strike_a_particle_in_the_path_once() {
+        // 150 degrees a substep, wrapped the way update_rectangle_rotation does; each blade's
+        // path crosses the particle within the frame
+        let mut world = strike_world(1.225, PropellerGeometry::default(), disk_position(2.0, 100.0));
+        let mut angle: f32 = 0.0;
+        let mut strikes = 0;
+        for _ in 0..4 {
+            if angle >= 360.0 {
+                angle -= 360.0;
+            }
+            strikes += sweep(&mut world, angle, angle + 150.0);
+            angle += 150.0;
+        }
+        assert_eq!(strikes, 1);
+    }
+
+    #[test]
+    fn spatial_grid_counts_the_contacts_all_pairs_does() {
+        let bounds = BoundingBox::default();
+        let contact = 2.0 * COLLISION_RADIUS;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        for count in [400, 2000] {
+            let positions: Vec<(Entity, Vec3)> = (0..count).map(|i| (Entity::from_raw(i as u32), random_domain_position(&mut rng, Vec3::ZERO, &bounds))).collect();
+            let mut all_pairs_contacts = 0;
+            for (i, &(_, a)) in positions.iter().enumerate() {
+                all_pairs_contacts += positions[i + 1..].iter().filter(|&&(_, b)| a.distance(b) <= contact).count();
+            }
+            // each pair once, as compare_particles takes them
+            let mut grid = SpatialGrid::new(bounds.volume(), count, COLLISION_RADIUS);
+            for &(entity, pos) in &positions {
+                grid.insert(entity, pos);
+            }
+            let grid_contacts: usize = positions.iter()
+                .map(|&(entity, pos)| grid.query_neighbors(pos, contact).filter(|&other| other > entity && pos.distance(positions[other.index() as usize].1) <= contact).count())
+                .sum();
+            assert_eq!(grid_contacts, all_pairs_contacts, "{} particles", count);
+        }
+    }
+
+    #[test]
+    fn csv_header_is_written_once() {
+        let path = std::env::temp_dir().join("propeller_csv_header_test.csv");
+        let _ = std::fs::remove_file(&path);
+        let output = CsvOutputConfig { file_path: path.to_string_lossy().into_owned(), write_header: true, delimiter: ';' };
+        let header = csv_header(2, &["average"]);
+        append_to_csv(&output, &header, &[60.0, 1.0, 2.0, 1.5]).unwrap();
+        append_to_csv(&output, &header, &[65.0, 2.0, 3.0, 2.5]).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "pitch_deg;trial_1;trial_2;average\n60;1;2;1.5\n65;2;3;2.5\n");
+    }
+
+    #[test]
+    fn non_ascii_delimiter_is_rejected() {
+        let config = SimConfig { csv_delimiter: '§', ..SimConfig::default() };
+        assert!(config.validate().unwrap_err().contains("csv_delimiter"));
+    }
+
+    #[test]
+    fn partial_pitch_dump_keeps_the_output_csv_shape() {
+        let state = SimulationState { data_row: vec![2.0], rev_per_sec_row: vec![10.0], power_row: vec![5.0], ..default() };
+        let config = SimConfig { trial_count: 3, ..default() };
+        let conditions = PitchConditions { density: 1.225, inflow_speed: 0.0, span: 1.0, disk_area: 1.0, blades_per_rotor: 2 };
+        let summary = state.pitch_summary(&config, 60.0, 3, &conditions);
+        let [record] = &summary.records[..] else { panic!("one row per pitch outside a parallel batch") };
+        assert_eq!(record.pitch, 60.0);
+        assert_eq!(record.trials[0], 2.0);
+        assert!(record.trials.len() == 3 && record.trials[1..].iter().all(|t| t.is_nan()));
+        assert_eq!(record.columns[0], ("mean_thrust".to_string(), 2.0 / config.trial_duration));
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(SimConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn short_warmup_with_the_default_startup_is_valid() {
+        let config = SimConfig { warmup_duration: 1.0, ..SimConfig::default() };
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_config_is_an_error() {
+        let config = SimConfig { trial_count: 0, ..SimConfig::default() };
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("trial_count"), "{}", err);
+    }
+
+    #[test]
+    fn single_trial_average_is_that_trial() {
+        let state = SimulationState { data_row: vec![1.5], rev_per_sec_row: vec![10.0], power_row: vec![5.0], ..default() };
+        let conditions = PitchConditions { density: 1.225, inflow_speed: 0.0, span: 1.0, disk_area: 1.0, blades_per_rotor: 2 };
+        let summary = state.pitch_summary(&SimConfig::default(), 60.0, 1, &conditions);
+        let record = &summary.records[0];
+        assert_eq!(record.trials, [1.5]);
+        assert_eq!(record.average, 1.5);
+        assert_eq!(csv_header(1, &["mean_thrust"]), ["pitch_deg", "trial_1", "mean_thrust"]);
+    }
+
+    #[test]
+    fn naca_0012_has_no_lift_but_drag_at_zero_angle() {
+        let profile = NacaProfile::default();
+        let (cl, cd) = profile.lookup_cl_cd(0.0);
+        assert_eq!(cl, 0.0);
+        assert!(cd > 0.0);
+        // symmetric section, and linear between rows
+        assert_eq!(profile.lookup_cl_cd(-5.0).0, -profile.lookup_cl_cd(5.0).0);
+        assert!((profile.lookup_cl_cd(3.0).0 - 0.33).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fourier_recovers_a_sinusoid_at_the_fundamental() {
+        // 2 s of 5 + 0.8 cos(2 pi 4 t + 0.6), sampled at 500 Hz
+        let signal = |frequency: f32| (0..1000).map(|k| {
+            let t = k as f32 * 0.002;
+            (t, 5.0 + 0.8 * (std::f32::consts::TAU * frequency * t + 0.6).cos())
+        }).collect::<Vec<_>>();
+        let fourier = FourierAnalysis::compute(&signal(4.0), 4.0);
+        let (amplitude, phase) = fourier.harmonics[0];
+        assert!((amplitude - 0.8).abs() < 1e-2, "{}", amplitude);
+        assert!((phase - 0.6).abs() < 1e-2, "{}", phase);
+        assert!(fourier.harmonics[1].0 < 1e-2);
+        assert!((fourier.peak_hz - 4.0).abs() < 0.5);
+        assert!(!fourier.peak_off_harmonic);
+
+        // 7.3 Hz is no multiple up to the third of a 2 Hz revolution rate
+        let fourier = FourierAnalysis::compute(&signal(7.3), 2.0);
+        assert!((fourier.peak_hz - 7.3).abs() < 0.5);
+        assert!(fourier.peak_off_harmonic);
+    }
+
+    #[test]
+    fn efficiency_plot_is_read_from_config() {
+        let config: SimConfig = toml::from_str("efficiency_plot = { file_path = \"sweep.svg\", width = 1200 }").unwrap();
+        assert_eq!(config.efficiency_plot.file_path, "sweep.svg");
+        assert_eq!((config.efficiency_plot.width, config.efficiency_plot.height), (1200, 600));
+    }
+
+    #[test]
+    fn turbulent_inflow_is_the_mean_wind() {
+        let wind = WindProfile::Turbulent { mean: Vec3::new(0.5, -2.0, 0.0), intensity: 1.0, length_scale: 1.0 };
+        assert_eq!(wind.inflow_speed(), 2.0);
+    }
+
+    #[test]
+    fn ground_effect_follows_cheeseman_bennett() {
+        assert!((compute_ground_effect_factor(1.0, 1.0) - 16.0 / 15.0).abs() < 1e-5);
+        assert!((compute_ground_effect_factor(0.5, 1.0) - 4.0 / 3.0).abs() < 1e-5);
+        assert_eq!(compute_ground_effect_factor(2.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn result_files_land_in_output_dir_in_order() {
+        let dir = std::env::temp_dir().join("propeller_output_dir_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut app = App::new();
+        app.insert_resource(DataLoggerConfig { output_dir: dir.clone(), format: OutputFormat::Csv })
+            .insert_resource(CsvOutputConfig::default())
+            .add_plugins(DataLoggerPlugin);
+        let logger = app.world.resource::<DataLogger>();
+        logger.send(LogRecord { pitch: 60.0, trials: vec![1.0], average: 1.0, columns: Vec::new() });
+        // sees the record already on disk
+        logger.write_file("test file", |dir| Ok(std::fs::copy(dir.join("output.csv"), dir.join("copy.csv")).map(|_| ())?));
+        logger.flush();
+        let copy = std::fs::read_to_string(dir.join("copy.csv")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(copy, "pitch_deg,trial_1\n60,1\n");
+    }
+
+    #[test]
+    fn tapered_beam_elements_follow_the_local_chord() {
+        let tapered = PropellerGeometry { taper_ratio: 0.5, ..PropellerGeometry::default() };
+        let beam = MeshDeformationSimulator::tapered(&tapered, 4, 70.0e9);
+        let widths: Vec<f32> = beam.elements.iter().map(|element| element.width).collect();
+        assert_eq!(widths, [0.9375, 0.8125, 0.6875, 0.5625]);
+        // the narrower tip is less stiff under the same loads
+        let loads = [1.0e3; 4];
+        let rectangular = MeshDeformationSimulator::tapered(&PropellerGeometry::default(), 4, 70.0e9);
+        assert!(beam.solve(&loads).0.abs() > rectangular.solve(&loads).0.abs());
+    }
+
+    #[test]
+    fn material_sets_the_beam_modulus() {
+        let custom = PropellerMaterial::parse("balsa:160:3.5e9").unwrap();
+        assert_eq!((custom.name.as_ref(), custom.density_kg_per_m3, custom.elastic_modulus), ("balsa", 160.0, 3.5e9));
+        assert!(PropellerMaterial::parse("balsa:160").is_err());
+        let geometry = PropellerGeometry::default();
+        let deflection = |material: PropellerMaterial| MeshDeformationSimulator::tapered(&geometry, 4, material.elastic_modulus).solve(&[1.0e3; 4]).0.abs();
+        assert!(deflection(PropellerMaterial::WOOD) > deflection(PropellerMaterial::CARBON_FIBER));
+    }
+
+    #[test]
+    fn same_seed_writes_the_same_csv() {
+        let run = |name: &str| {
+            let output_dir = std::env::temp_dir().join(name);
+            let _ = std::fs::remove_dir_all(&output_dir);
+            let config = SimConfig {
+                particle_count: 200,
+                pitch_start: 50.0,
+                pitch_end: 60.0,
+                pitch_step: 10.0,
+                trial_count: 1,
+                trial_duration: 0.2,
+                warmup_duration: 0.1,
+                time_scale: 10.0,
+                seed: Some(7),
+                output_dir: output_dir.clone(),
+                ..SimConfig::default()
+            };
+            PropellerTestRig::run(config).unwrap();
+            let bytes = std::fs::read(output_dir.join("output.csv")).unwrap();
+            std::fs::remove_dir_all(&output_dir).unwrap();
+            bytes
+        };
+        let first = run("propeller_determinism_a");
+        assert!(!first.is_empty());
+        assert_eq!(first, run("propeller_determinism_b"));
+    }
+
+    #[test]
+    fn azimuth_of_covers_all_four_quadrants() {
+        let quarter = std::f32::consts::FRAC_PI_4;
+        // +Z is 0 and +X a quarter turn, as a blade at rotation r points along (sin r, 0, cos r)
+        assert!((azimuth_of(Vec3::new(1.0, 0.0, 1.0)) - quarter).abs() < 1e-6);
+        assert!((azimuth_of(Vec3::new(1.0, 0.0, -1.0)) - 3.0 * quarter).abs() < 1e-6);
+        assert!((azimuth_of(Vec3::new(-1.0, 0.0, -1.0)) - 5.0 * quarter).abs() < 1e-6);
+        assert!((azimuth_of(Vec3::new(-1.0, 0.0, 1.0)) - 7.0 * quarter).abs() < 1e-6);
+    }
+
+    #[test]
+    fn strikes_across_the_wrap() {
+        // the arc runs from 355 to 365 before update_rectangle_rotation wraps it
+        for angle in [358.0, 2.0] {
+            let mut world = strike_world(1.225, PropellerGeometry::default(), disk_position(2.0, angle));
+            assert_eq!(sweep(&mut world, 355.0, 365.0), 1, "particle at {} degrees", angle);
+        }
+        let mut world = strike_world(1.225, PropellerGeometry::default(), disk_position(2.0, 90.0));
+        assert_eq!(sweep(&mut world, 355.0, 365.0), 0);
+    }
+
+    #[test]
+    fn zero_sweep_strikes_like_a_straight_blade() {
+        let geometry = PropellerGeometry { sweep_angle_deg: 0.0, ..PropellerGeometry::default() };
+        let [root_trailing, root_leading, tip_leading, tip_trailing] = geometry.planform_corners();
+        assert_eq!((root_leading.y, root_trailing.y), (tip_leading.y, tip_trailing.y));
+        // the window of the unswept blade: within half the projected chord of the arc, per blade
+        let radius = 2.0;
+        let modifier = (geometry.chord * 10f32.to_radians().cos() / (2.0 * radius)).atan().to_degrees();
+        for angle in (0..36).map(|i| i as f32 * 10.0 + 5.0) {
+            let expected = [0.0, 180.0].iter().any(|azimuth| {
+                let theta = (angle - azimuth).rem_euclid(360.0);
+                theta > 20.0 - modifier && theta < 40.0 + modifier
+            });
+            let mut world = strike_world(1.225, geometry, disk_position(radius, angle));
+            assert_eq!(sweep(&mut world, 20.0, 40.0), expected as usize, "particle at {} degrees", angle);
+        }
+    }
+
+    #[test]
+    fn counter_clockwise_strikes_across_the_wrap() {
+        // rotation_z runs below zero, from 5 to -5, before it wraps
+        for angle in [358.0, 2.0] {
+            let mut world = strike_world(1.225, PropellerGeometry::default(), disk_position(2.0, angle));
+            for mut hub in world.query::<&mut PropellerHub>().iter_mut(&mut world) {
+                hub.rotation_direction = RotationDirection::CounterClockwise;
+            }
+            assert_eq!(sweep(&mut world, 5.0, -5.0), 1, "particle at {} degrees", angle);
+        }
+    }
+    #[test]
+    fn thrust_controller_does_not_wind_up_at_the_pitch_limit() {
+        let controller = ThrustController { target_thrust: 1000.0, kp: 0.1, ki: 0.02, kd: 0.0 };
+        let mut state = ThrustControllerState::default();
+        let mut pitch = 80.0;
+        // an unreachable target keeps the pitch pinned at 90
+        for _ in 0..20 {
+            pitch = state.next_pitch(&controller, 0.0, pitch);
+        }
+        assert_eq!(pitch, 90.0);
+        assert_eq!(state.integral, 0.0);
+        // thrust over the target pulls it straight back off the limit
+        assert!(state.next_pitch(&controller, 1010.0, pitch) < 90.0);
+    }
+
+    #[test]
+    fn maxwell_boltzmann_start_clears_the_speed_clamp() {
+        let velocity_distribution = VelocityDist::MaxwellBoltzmann { temperature: 288.15, mass: 4.81e-26 };
+        let config = SimConfig { particle_init: ParticleInitConfig { velocity_distribution }, ..SimConfig::default() };
+        let fluid = config.fluid();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let fastest = (0..1000).map(|_| velocity_distribution.sample(&fluid, &mut rng).length()).fold(0.0, f32::max);
+        assert!(fastest > DEFAULT_MAX_PARTICLE_SPEED && fastest < config.max_particle_speed(), "fastest {} against {}", fastest, config.max_particle_speed());
+        assert_eq!(SimConfig::default().max_particle_speed(), DEFAULT_MAX_PARTICLE_SPEED);
+    }
+
+    #[test]
+    fn acoustic_tip_mach_follows_the_speed_of_sound_and_scale() {
+        // 10 rev/s at a 1 m tip is 62.8 m/s
+        let tip_mach = |radius_m, speed_of_sound| PropellerAcoustics::compute(1.0, 10.0, radius_m, 1.0, 1.225, 2, speed_of_sound).tip_mach;
+        assert!((tip_mach(1.0, 343.0) - std::f32::consts::TAU * 10.0 / 343.0).abs() < 1e-6);
+        assert!((tip_mach(1.0, 1481.0) - std::f32::consts::TAU * 10.0 / 1481.0).abs() < 1e-6);
+        assert!((tip_mach(0.5, 343.0) - 0.5 * tip_mach(1.0, 343.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn harmonic_phases_average_round_the_circle() {
+        let phases = [170f32.to_radians(), (-170f32).to_radians()];
+        assert!((circular_mean(phases.into_iter()).abs() - std::f32::consts::PI).abs() < 1e-5);
+        let phases = [0.1, 0.3, 0.2];
+        assert!((circular_mean(phases.into_iter()) - 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn leaving_manual_mode_restores_the_sweep_pitch_and_restarts_the_trial() {
+        let mut world = World::new();
+        let config = SimConfig::default();
+        world.insert_resource(SimulationPhase::Collecting { steps: 120 });
+        world.insert_resource(ManualControl(false));
+        world.init_resource::<ManualMeasurement>();
+        let hub = world.spawn_empty().id();
+        let blade = world.spawn(PropellerBlade { hub, pitch: 40.0, azimuth: 0.0, offset: Vec3::ZERO, length: 1.0, elements: Vec::new() }).id();
+        let mut keys = Input::<KeyCode>::default();
+        keys.press(KeyCode::M);
+        world.insert_resource(keys);
+        world.insert_resource(config.clone());
+        world.run_system_once(toggle_manual_control);
+        assert!(world.resource::<ManualControl>().0);
+
+        world.get_mut::<PropellerBlade>(blade).unwrap().pitch = 55.0;
+        world.run_system_once(toggle_manual_control);
+        assert!(!world.resource::<ManualControl>().0);
+        assert_eq!(world.get::<PropellerBlade>(blade).unwrap().pitch, 40.0);
+        assert!(matches!(*world.resource::<SimulationPhase>(), SimulationPhase::Warmup { steps: 0 }));
+    }
+
+    #[test]
+    fn particle_at_a_face_is_reflected() {
+        let boundaries = BoundaryConditions([BoundaryCondition::Reflect; 3]);
+        let bounds = BoundingBox { half_extent: Vec3::new(5.0, 2.0, 5.0) };
+        let mut particle = Particle { velocity: Vec3::new(0.0, 3.0, -1.0), mass: 1.0, domain_center: Vec3::ZERO };
+        // exactly on the top face, and past the -Z one
+        let mut transform = Transform::from_xyz(1.0, 2.0, -5.5);
+        assert!(apply_boundaries(&boundaries, &bounds, &mut transform, &mut particle).is_none());
+        assert_eq!(transform.translation, Vec3::new(1.0, 2.0, -5.0));
+        assert_eq!(particle.velocity, Vec3::new(0.0, -3.0, 1.0));
+        // heading back in, it is left alone
+        apply_boundaries(&boundaries, &bounds, &mut transform, &mut particle);
+        assert_eq!(particle.velocity, Vec3::new(0.0, -3.0, 1.0));
+    }
+
+    #[test]
+    fn duct_contact_on_each_face() {
+        let duct = DuctGeometry { inner_radius: 4.5, outer_radius: 5.0, length: 2.0 };
+        let contact = |rel: Vec3| duct.contact(rel, 0.0).map(|(normal, depth)| (normal, (depth * 100.0).round() / 100.0));
+        assert_eq!(contact(Vec3::new(4.6, 0.0, 0.0)), Some((Vec3::NEG_X, 0.1)));
+        assert_eq!(contact(Vec3::new(0.0, 0.0, 4.9)), Some((Vec3::Z, 0.1)));
+        assert_eq!(contact(Vec3::new(4.75, 0.9, 0.0)), Some((Vec3::Y, 0.1)));
+        assert_eq!(contact(Vec3::new(4.75, -0.9, 0.0)), Some((Vec3::NEG_Y, 0.1)));
+        assert_eq!(contact(Vec3::new(4.0, 0.0, 0.0)), None);
+        assert_eq!(contact(Vec3::new(4.75, 1.5, 0.0)), None);
+    }
+
+    #[test]
+    fn duct_reflects_in_the_tilted_disk_frame() {
+        let duct = DuctGeometry { inner_radius: 4.5, outer_radius: 5.0, length: 2.0 };
+        let hub = Vec3::new(1.0, 2.0, 3.0);
+        // a vertical disk, its spin axis along world Z
+        let to_world = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+        let mut transform = Transform::from_translation(hub + to_world * Vec3::new(4.75, 0.95, 0.0));
+        let mut particle = Particle { velocity: to_world * Vec3::new(0.0, -2.0, 0.0), mass: 0.5, domain_center: Vec3::ZERO };
+        let impulse = duct.collide(hub, to_world, &mut transform, &mut particle).unwrap();
+        // out of the upper lip, and the duct is pushed the way the particle was going
+        let rel = to_world.inverse() * (transform.translation - hub);
+        assert!((rel.y - (1.0 + COLLISION_RADIUS)).abs() < 1e-5);
+        assert!((particle.velocity - Vec3::new(0.0, 0.0, 2.0)).length() < 1e-5);
+        assert!((impulse - Vec3::new(0.0, 0.0, -2.0)).length() < 1e-5);
+        // on its way out again, it is moved clear but not reflected
+        transform.translation = hub + to_world * Vec3::new(4.75, 0.95, 0.0);
+        assert_eq!(duct.collide(hub, to_world, &mut transform, &mut particle), None);
+        assert!((particle.velocity - Vec3::new(0.0, 0.0, 2.0)).length() < 1e-5);
+    }
+    #[test]
+    fn spatial_grid_finds_every_contact_at_any_particle_count() {
+        let bounds = BoundingBox::default();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let contact = 2.0 * COLLISION_RADIUS;
+        let mut cell_sizes = Vec::new();
+        for count in [10, 400, 4000] {
+            let positions: Vec<(Entity, Vec3)> = (0..count).map(|i| (Entity::from_raw(i as u32), random_domain_position(&mut rng, Vec3::ZERO, &bounds))).collect();
+            let mut grid = SpatialGrid::new(bounds.volume(), count, COLLISION_RADIUS);
+            for &(entity, pos) in &positions {
+                grid.insert(entity, pos);
+            }
+            let mut candidates = 0;
+            for &(entity, pos) in &positions {
+                let nearby: Vec<Entity> = grid.query_neighbors(pos, contact).collect();
+                candidates += nearby.len();
+                for &(other, other_pos) in &positions {
+                    if other != entity && pos.distance(other_pos) < contact {
+                        assert!(nearby.contains(&other), "{} particles: missed a contact", count);
+                    }
+                }
+            }
+            // the grid keeps the pair checks well under all pairs
+            if count == 4000 {
+                assert!(candidates < count * count / 10, "{} candidates for {} particles", candidates, count);
+            }
+            cell_sizes.push(grid.cell_size);
+        }
+        assert!(cell_sizes.windows(2).all(|w| w[1] < w[0]), "cells should shrink as particles are added: {:?}", cell_sizes);
+    }
+
+    #[test]
+    fn collision_intervals_use_the_fixed_step_time() {
+        let mut world = World::new();
+        world.init_resource::<Events<ParticleParticleCollision>>();
+        world.init_resource::<ParticleParticleCollisionStats>();
+        let particle = || (Particle { velocity: Vec3::X, mass: 1.0, domain_center: Vec3::ZERO }, LastCollisionTime::default());
+        let (a, b, c) = (world.spawn(particle()).id(), world.spawn(particle()).id(), world.spawn(particle()).id());
+        // three fixed steps inside one frame, a hits b on the first and c on the third
+        world.send_event(ParticleParticleCollision { entity_a: a, entity_b: b, time: 0.01 });
+        world.send_event(ParticleParticleCollision { entity_a: a, entity_b: c, time: 0.03 });
+        world.run_system_once(track_particle_collisions);
+        let stats = world.resource::<ParticleParticleCollisionStats>();
+        assert_eq!(stats.intervals, 1);
+        assert!((stats.mean_time_between_collisions - 0.02).abs() < 1e-6);
+    }
+
+    #[test]
+    fn alternate_rotors_hold_their_share_of_target_rpm() {
+        let array = PropellerArray { count: 3, alternate_rpm_ratio: Some(0.8), ..default() };
+        assert_eq!((0..3).map(|i| array.rpm_ratio(i)).collect::<Vec<_>>(), vec![1.0, 0.8, 1.0]);
+        assert_eq!(PropellerArray::default().rpm_ratio(1), 1.0);
+
+        let mut config = SimConfig { propeller_array: array, ..default() };
+        assert!(config.validate().unwrap_err().contains("ConstantRPM"));
+        config.rotor_governor = Some(RotorGovernor::ConstantRPM { target_rpm: 600.0, kp: 50.0, ki: 10.0, kd: 0.0 });
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn counter_rotating_torques_cancel_in_the_net_torque() {
+        let state = SimulationState {
+            data_row: vec![2.0],
+            rev_per_sec_row: vec![10.0],
+            power_row: vec![5.0],
+            harmonics_row: vec![vec![(1.0, 0.0); FourierAnalysis::HARMONICS]],
+            rotor_pitches: vec![60.0, 60.0],
+            rotor_rows: vec![vec![2.0], vec![2.0]],
+            rotor_torque_rows: vec![vec![3.0], vec![-2.5]],
+            ..default()
+        };
+        let config = SimConfig::default();
+        let conditions = PitchConditions { density: 1.225, inflow_speed: 0.0, span: 1.0, disk_area: 1.0, blades_per_rotor: 2 };
+        let summary = state.pitch_summary(&config, 60.0, 1, &conditions);
+        let expected = 0.5 / config.trial_duration;
+        assert!((summary.results[0].net_torque - expected).abs() < 1e-6);
+        assert!(summary.records[0].columns.contains(&("net_reaction_torque".to_string(), summary.results[0].net_torque)));
+    }
+}
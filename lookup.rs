@@ -0,0 +1,56 @@
+use crate::PitchResult;
+
+// Pitch that gives target_thrust (N per rotor) on the measured thrust against pitch curve,
+// linear between neighbouring sweep points. The lowest such pitch when the curve is not
+// monotonic, None outside the measured thrust range.
+pub fn interpolate_pitch_for_thrust(target_thrust: f32, results: &[PitchResult]) -> Option<f32> {
+    let mut points: Vec<(f32, f32)> = results.iter().map(|r| (r.pitch, r.mean)).collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if let [(pitch, thrust)] = points[..] {
+        return (thrust == target_thrust).then_some(pitch);
+    }
+    points.windows(2).find_map(|pair| {
+        let ((p0, t0), (p1, t1)) = (pair[0], pair[1]);
+        if target_thrust < t0.min(t1) || target_thrust > t0.max(t1) {
+            return None;
+        }
+        // flat segment, every pitch on it gives the target
+        if t0 == t1 {
+            return Some(p0);
+        }
+        Some(p0 + (target_thrust - t0) / (t1 - t0) * (p1 - p0))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PropellerCoefficients;
+
+    fn sweep(points: &[(f32, f32)]) -> Vec<PitchResult> {
+        points.iter().map(|&(pitch, thrust)| PitchResult::new(pitch, vec![thrust], &PropellerCoefficients::default(), 0.0)).collect()
+    }
+
+    #[test]
+    fn endpoints_give_their_own_pitch() {
+        let results = sweep(&[(60.0, 3.0), (50.0, 1.0), (70.0, 4.0)]);
+        assert_eq!(interpolate_pitch_for_thrust(1.0, &results), Some(50.0));
+        assert_eq!(interpolate_pitch_for_thrust(4.0, &results), Some(70.0));
+    }
+
+    #[test]
+    fn midpoints_interpolate_linearly() {
+        let results = sweep(&[(50.0, 1.0), (60.0, 3.0), (70.0, 4.0)]);
+        assert_eq!(interpolate_pitch_for_thrust(2.0, &results), Some(55.0));
+        assert_eq!(interpolate_pitch_for_thrust(3.5, &results), Some(65.0));
+    }
+
+    #[test]
+    fn out_of_range_thrust_has_no_pitch() {
+        let results = sweep(&[(50.0, 1.0), (60.0, 3.0)]);
+        assert_eq!(interpolate_pitch_for_thrust(0.5, &results), None);
+        assert_eq!(interpolate_pitch_for_thrust(3.5, &results), None);
+        assert_eq!(interpolate_pitch_for_thrust(1.0, &[]), None);
+        assert_eq!(interpolate_pitch_for_thrust(2.0, &sweep(&[(50.0, 1.0)])), None);
+    }
+}
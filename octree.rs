@@ -0,0 +1,151 @@
+use bevy::prelude::{Entity, Resource, Vec3};
+
+// Points deeper than this share a leaf however many there are
+const MAX_DEPTH: u32 = 6;
+// Points a leaf holds before it splits
+const LEAF_CAPACITY: usize = 8;
+
+struct Node {
+    center: Vec3,
+    half_size: f32,
+    points: Vec<(Entity, Vec3)>,
+    children: Option<Box<[Node; 8]>>,
+}
+
+impl Node {
+    fn new(center: Vec3, half_size: f32) -> Self {
+        Node { center, half_size, points: Vec::new(), children: None }
+    }
+
+    // bit 0 is +x, bit 1 +y, bit 2 +z
+    fn octant(&self, pos: Vec3) -> usize {
+        (pos.x >= self.center.x) as usize | ((pos.y >= self.center.y) as usize) << 1 | ((pos.z >= self.center.z) as usize) << 2
+    }
+
+    fn insert(&mut self, entity: Entity, pos: Vec3, depth: u32) {
+        let octant = self.octant(pos);
+        if let Some(children) = self.children.as_mut() {
+            children[octant].insert(entity, pos, depth + 1);
+            return;
+        }
+        self.points.push((entity, pos));
+        if self.points.len() > LEAF_CAPACITY && depth < MAX_DEPTH {
+            self.split(depth);
+        }
+    }
+
+    fn split(&mut self, depth: u32) {
+        let quarter = self.half_size / 2.0;
+        let center = self.center;
+        let child = |i: usize| {
+            let sign = |bit: usize| if i & bit != 0 { 1.0 } else { -1.0 };
+            Node::new(center + Vec3::new(sign(1), sign(2), sign(4)) * quarter, quarter)
+        };
+        self.children = Some(Box::new([child(0), child(1), child(2), child(3), child(4), child(5), child(6), child(7)]));
+        for (entity, pos) in std::mem::take(&mut self.points) {
+            self.insert(entity, pos, depth);
+        }
+    }
+
+    fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        let min = self.center - Vec3::splat(self.half_size);
+        let max = self.center + Vec3::splat(self.half_size);
+        center.clamp(min, max).distance_squared(center) <= radius * radius
+    }
+
+    fn query_sphere(&self, center: Vec3, radius: f32, out: &mut Vec<Entity>) {
+        if !self.intersects_sphere(center, radius) {
+            return;
+        }
+        for &(entity, pos) in &self.points {
+            if pos.distance_squared(center) <= radius * radius {
+                out.push(entity);
+            }
+        }
+        if let Some(children) = self.children.as_ref() {
+            for child in children.iter() {
+                child.query_sphere(center, radius, out);
+            }
+        }
+    }
+}
+
+// Particle positions partitioned over a cube, rebuilt every physics step
+#[derive(Resource)]
+pub struct Octree {
+    root: Node,
+    // points outside the cube, always checked
+    outside: Vec<(Entity, Vec3)>,
+}
+
+impl Octree {
+    pub fn new(center: Vec3, half_size: f32) -> Self {
+        Octree { root: Node::new(center, half_size), outside: Vec::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::new(self.root.center, self.root.half_size);
+        self.outside.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3) {
+        if (pos - self.root.center).abs().max_element() > self.root.half_size {
+            self.outside.push((entity, pos));
+        } else {
+            self.root.insert(entity, pos, 0);
+        }
+    }
+
+    pub fn query_sphere(&self, center: Vec3, radius: f32) -> Vec<Entity> {
+        let mut found = Vec::new();
+        self.root.query_sphere(center, radius, &mut found);
+        for &(entity, pos) in &self.outside {
+            if pos.distance_squared(center) <= radius * radius {
+                found.push(entity);
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn random_points(count: u32, extent: f32) -> Vec<(Entity, Vec3)> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        (0..count).map(|i| (Entity::from_raw(i), Vec3::new(rng.gen_range(-extent..extent), rng.gen_range(-extent..extent), rng.gen_range(-extent..extent)))).collect()
+    }
+
+    fn brute_force(points: &[(Entity, Vec3)], center: Vec3, radius: f32) -> Vec<Entity> {
+        let mut found: Vec<Entity> = points.iter().filter(|(_, pos)| pos.distance_squared(center) <= radius * radius).map(|&(entity, _)| entity).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn sphere_queries_match_brute_force() {
+        // some points fall outside the cube and go to the always-checked list
+        let points = random_points(2000, 6.0);
+        let mut octree = Octree::new(Vec3::ZERO, 5.0);
+        for &(entity, pos) in &points {
+            octree.insert(entity, pos);
+        }
+        for (center, radius) in [(Vec3::ZERO, 1.0), (Vec3::new(4.5, -4.5, 4.5), 2.0), (Vec3::new(-1.0, 2.0, 0.5), 0.3), (Vec3::splat(5.5), 1.5), (Vec3::ZERO, 20.0)] {
+            let mut found = octree.query_sphere(center, radius);
+            found.sort();
+            assert_eq!(found, brute_force(&points, center, radius), "sphere at {} radius {}", center, radius);
+        }
+    }
+
+    #[test]
+    fn clear_empties_the_tree() {
+        let mut octree = Octree::new(Vec3::ZERO, 5.0);
+        for (entity, pos) in random_points(100, 6.0) {
+            octree.insert(entity, pos);
+        }
+        octree.clear();
+        assert!(octree.query_sphere(Vec3::ZERO, 20.0).is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{OptimizerMode, PitchControl, PropellerTestRig, RotorGovernor, SimConfig};
+use std::path::Path;
+
+// dT/d(parameter) of one design parameter, by forward difference about the base design
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sensitivity {
+    pub parameter: &'static str,
+    pub value: f32,
+    pub step: f32,
+    // mean thrust per rotor at value + step, N
+    pub thrust: f32,
+    pub derivative: f32,
+    // percent change in thrust per percent change in the parameter, comparable across units
+    pub elasticity: f32,
+}
+
+impl Sensitivity {
+    fn new(parameter: &'static str, value: f32, step: f32, base_thrust: f32, thrust: f32) -> Self {
+        let derivative = (thrust - base_thrust) / step;
+        let elasticity = if base_thrust != 0.0 { derivative * value / base_thrust } else { 0.0 };
+        Sensitivity { parameter, value, step, thrust, derivative, elasticity }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SensitivityMatrix {
+    pub pitch: f32,
+    pub base_thrust: f32,
+    pub rows: Vec<Sensitivity>,
+}
+
+impl SensitivityMatrix {
+    // the parameter thrust responds to most, by elasticity so degrees and kilograms compare
+    pub fn dominant(&self) -> Option<&Sensitivity> {
+        self.rows.iter().max_by(|a, b| a.elasticity.abs().total_cmp(&b.elasticity.abs()))
+    }
+
+    pub fn write_csv(&self, file_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut wtr = csv::Writer::from_path(file_path)?;
+        wtr.write_record(["parameter", "value", "step", "thrust", "dT_dparam", "elasticity"])?;
+        for row in &self.rows {
+            wtr.write_record([row.parameter.to_string(), row.value.to_string(), row.step.to_string(), row.thrust.to_string(), row.derivative.to_string(), row.elasticity.to_string()])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+// One design parameter: its value in a config and how to set it
+struct Parameter {
+    name: &'static str,
+    get: fn(&SimConfig) -> f32,
+    set: fn(&mut SimConfig, f32),
+}
+
+// The rotor speed under ConstantRPM; a ConstantPower rotor has no set speed, so its shaft
+// power stands in
+fn speed_parameter(config: &SimConfig) -> Parameter {
+    match config.rotor_governor {
+        Some(RotorGovernor::ConstantRPM { .. }) => Parameter {
+            name: "rpm",
+            get: |config| match config.rotor_governor {
+                Some(RotorGovernor::ConstantRPM { target_rpm, .. }) => target_rpm,
+                _ => unreachable!(),
+            },
+            set: |config, value| {
+                if let Some(RotorGovernor::ConstantRPM { target_rpm, .. }) = &mut config.rotor_governor {
+                    *target_rpm = value;
+                }
+            },
+        },
+        _ => Parameter {
+            name: "power_input",
+            get: |config| match config.rotor_governor {
+                Some(RotorGovernor::ConstantPower(power)) => power,
+                _ => config.power_input,
+            },
+            set: |config, value| config.rotor_governor = Some(RotorGovernor::ConstantPower(value)),
+        },
+    }
+}
+
+fn parameters(config: &SimConfig) -> Vec<Parameter> {
+    vec![
+        Parameter { name: "pitch_deg", get: |config| config.first_pitch(), set: |config, value| config.pitch_values = Some(vec![value]) },
+        speed_parameter(config),
+        Parameter { name: "chord", get: |config| config.geometry.chord, set: |config, value| config.geometry.chord = value },
+        // tip to tip, each blade is one span long
+        Parameter { name: "diameter", get: |config| 2.0 * config.geometry.span, set: |config, value| config.geometry.span = value / 2.0 },
+        Parameter { name: "propeller_mass", get: |config| config.propeller_mass, set: |config, value| config.propeller_mass = value },
+    ]
+}
+
+pub struct ParameterSensitivityMatrix;
+
+impl ParameterSensitivityMatrix {
+    // Runs the rig at the first pitch of base_config, then once more per parameter with
+    // that parameter raised by eps of its value. Every run keeps its result files in
+    // output_dir/sensitivity/<parameter>.
+    pub fn compute(base_config: &SimConfig, eps: f32) -> Result<SensitivityMatrix, String> {
+        if !(eps.is_finite() && eps > 0.0) {
+            return Err(format!("the sensitivity step must be a positive fraction, got {}", eps));
+        }
+        base_config.validate()?;
+        let mut base = base_config.clone();
+        // a single fixed sweep point, so every run measures the same design
+        base.pitch_values = Some(vec![base_config.first_pitch()]);
+        base.batch_mode = None;
+        base.pitch_control = PitchControl::Sweep;
+        base.pitch_optimizer.mode = OptimizerMode::LinearSweep;
+        let output_dir = base_config.output_dir.join("sensitivity");
+
+        let run = |mut config: SimConfig, name: &str| -> Result<f32, String> {
+            config.output_dir = output_dir.join(name);
+            let results = PropellerTestRig::run(config)?;
+            results.first().map(|result| result.mean).ok_or_else(|| format!("the {} run finished no pitch", name))
+        };
+        let base_thrust = run(base.clone(), "base")?;
+        let mut rows = Vec::new();
+        for parameter in parameters(&base) {
+            let value = (parameter.get)(&base);
+            // a parameter at zero has no scale, step it by eps itself
+            let step = if value != 0.0 { eps * value.abs() } else { eps };
+            let mut config = base.clone();
+            (parameter.set)(&mut config, value + step);
+            let thrust = run(config, parameter.name)?;
+            rows.push(Sensitivity::new(parameter.name, value, step, base_thrust, thrust));
+        }
+        Ok(SensitivityMatrix { pitch: base.first_pitch(), base_thrust, rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_difference_and_elasticity() {
+        let row = Sensitivity::new("chord", 2.0, 0.1, 10.0, 10.5);
+        assert!((row.derivative - 5.0).abs() < 1e-4);
+        assert!((row.elasticity - 1.0).abs() < 1e-4);
+        assert_eq!(Sensitivity::new("chord", 2.0, 0.1, 0.0, 0.5).elasticity, 0.0);
+    }
+
+    #[test]
+    fn dominant_parameter_compares_elasticities() {
+        let matrix = SensitivityMatrix {
+            pitch: 30.0,
+            base_thrust: 10.0,
+            // mass has the larger derivative but the smaller relative effect
+            rows: vec![Sensitivity::new("propeller_mass", 5.0, 0.25, 10.0, 10.5), Sensitivity::new("diameter", 8.0, 0.4, 10.0, 9.4)],
+        };
+        assert_eq!(matrix.dominant().map(|row| row.parameter), Some("diameter"));
+    }
+
+    #[test]
+    fn every_parameter_round_trips_through_its_setter() {
+        for governor in [None, Some(RotorGovernor::ConstantRPM { target_rpm: 600.0, kp: 1.0, ki: 0.0, kd: 0.0 })] {
+            let mut config = SimConfig { rotor_governor: governor, ..SimConfig::default() };
+            for parameter in parameters(&config) {
+                let value = (parameter.get)(&config) + 1.0;
+                (parameter.set)(&mut config, value);
+                assert_eq!((parameter.get)(&config), value, "{}", parameter.name);
+            }
+        }
+    }
+}
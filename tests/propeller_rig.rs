@@ -0,0 +1,41 @@
+// Whole headless sweeps through the library, too slow for every run: cargo test -- --ignored
+use fluid_density_propeller_simulator::{PitchResult, PropellerTestRig, SimConfig};
+
+// A short seeded sweep writing into its own temporary output_dir, the rotor held at 600 rpm
+// so the blade strikes stand out from the particle noise. The speed clamp sits well above that
+// rotor's 250 m/s tip speed, so struck particles keep their impulse. Counter-clockwise, the
+// blades push the fluid down. extra holds the keys the test varies.
+fn sweep(name: &str, extra: &str) -> Vec<PitchResult> {
+    let output_dir = std::env::temp_dir().join(name);
+    let text = format!(
+        "particle_count = 4000\ntrial_count = 3\ntrial_duration = 2.0\nwarmup_duration = 1.0\nseed = 1\nmax_particle_speed = 600.0\noutput_dir = {:?}\n\
+         rotor_governor = {{ ConstantRPM = {{ target_rpm = 600.0, kp = 50.0, ki = 10.0, kd = 0.0 }} }}\n\
+         propeller_array = {{ count = 1, arrangement = {{ Grid = {{ spacing = 8.0 }} }}, rotation_direction = \"CounterClockwise\", counter_rotating = false }}\n{}",
+        output_dir, extra
+    );
+    let results = PropellerTestRig::run(SimConfig::from_toml(&text).unwrap()).unwrap();
+    let _ = std::fs::remove_dir_all(&output_dir);
+    results
+}
+
+#[test]
+#[ignore]
+fn thrust_increases_with_pitch_in_air() {
+    let results = sweep("propeller_rig_pitch", "fluid_density = 1.225\npitch_values = [10.0, 20.0, 30.0]");
+    assert_eq!(results.len(), 3);
+    assert!(results[0].mean > 0.0, "{} N at {} deg", results[0].mean, results[0].pitch);
+    for pair in results.windows(2) {
+        assert!(pair[1].mean > pair[0].mean, "{} N at {} deg, {} N at {} deg", pair[0].mean, pair[0].pitch, pair[1].mean, pair[1].pitch);
+    }
+}
+
+#[test]
+#[ignore]
+fn thrust_coefficient_is_density_invariant() {
+    // held at one speed, so only the fluid changes between the runs
+    let air = sweep("propeller_rig_air", "fluid_density = 1.225\npitch_values = [30.0]");
+    let dense = sweep("propeller_rig_dense", "fluid_density = 2.45\npitch_values = [30.0]");
+    let (ct_air, ct_dense) = (air[0].ct, dense[0].ct);
+    assert!(ct_air > 0.0);
+    assert!((ct_dense - ct_air).abs() / ct_air < 0.15, "CT {} in air, {} at twice the density", ct_air, ct_dense);
+}
@@ -0,0 +1,151 @@
+use crate::{ArrayArrangement, OptimizerMode, PitchControl, PropellerTestRig, RotorGovernor, SimConfig};
+use std::path::Path;
+
+// Lower over upper rotor RPM the bisection starts from, wide enough to bracket the balance
+// of two identical rotors at any pitch
+pub const RATIO_BRACKET: (f32, f32) = (0.5, 2.0);
+const MAX_ITERATIONS: u32 = 12;
+
+// Where a bisection stopped: the argument, the function there and whether it met the tolerance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BisectionResult {
+    pub x: f32,
+    pub value: f32,
+    pub converged: bool,
+}
+
+// Bisects f over [low, high] until |f| < tolerance, giving up after max_iterations halvings
+// with the last midpoint. None when f has the same sign at both ends.
+fn bisect(mut low: f32, mut high: f32, tolerance: f32, max_iterations: u32, mut f: impl FnMut(f32) -> Result<f32, String>) -> Result<Option<BisectionResult>, String> {
+    let mut f_low = f(low)?;
+    if f_low.abs() < tolerance {
+        return Ok(Some(BisectionResult { x: low, value: f_low, converged: true }));
+    }
+    let f_high = f(high)?;
+    if f_high.abs() < tolerance {
+        return Ok(Some(BisectionResult { x: high, value: f_high, converged: true }));
+    }
+    if f_low.signum() == f_high.signum() {
+        return Ok(None);
+    }
+    let mut root = BisectionResult { x: low, value: f_low, converged: false };
+    for _ in 0..max_iterations {
+        let mid = 0.5 * (low + high);
+        let f_mid = f(mid)?;
+        root = BisectionResult { x: mid, value: f_mid, converged: f_mid.abs() < tolerance };
+        if root.converged {
+            break;
+        }
+        if f_mid.signum() == f_low.signum() {
+            low = mid;
+            f_low = f_mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(Some(root))
+}
+
+// The balancing lower over upper RPM of one pitch, None when the bracket holds no balance
+#[derive(Clone, Debug)]
+pub struct TorqueBalance {
+    pub pitch: f32,
+    pub balance: Option<BisectionResult>,
+}
+
+pub fn write_torque_balance_csv(file_path: &Path, balances: &[TorqueBalance]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_path(file_path)?;
+    wtr.write_record(["pitch_deg", "rpm_ratio", "net_torque", "converged"])?;
+    for balance in balances {
+        let (ratio, torque, converged) = match balance.balance {
+            Some(root) => (root.x.to_string(), root.value.to_string(), root.converged.to_string()),
+            None => (String::new(), String::new(), false.to_string()),
+        };
+        wtr.write_record([balance.pitch.to_string(), ratio, torque, converged])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+pub struct DualRotorTorqueBalance;
+
+impl DualRotorTorqueBalance {
+    // For every pitch of the sweep, holds the upper rotor of a counter-rotating Tandem pair
+    // at the ConstantRPM target_rpm and bisects the lower rotor's RPM until the net reaction
+    // torque is below tolerance, N m. Each step is a full rig run, its result files land in
+    // output_dir/torque_balance.
+    pub fn run(base_config: &SimConfig, tolerance: f32) -> Result<Vec<TorqueBalance>, String> {
+        if !(tolerance.is_finite() && tolerance > 0.0) {
+            return Err(format!("the torque balance tolerance must be positive, got {}", tolerance));
+        }
+        base_config.validate()?;
+        let Some(RotorGovernor::ConstantRPM { target_rpm: upper_rpm, kp, ki, kd }) = base_config.rotor_governor else {
+            return Err("the torque balance holds the upper rotor with a ConstantRPM rotor_governor".to_string());
+        };
+        if !matches!(base_config.propeller_array.arrangement, ArrayArrangement::Tandem { .. }) {
+            return Err("the torque balance needs a coaxial pair, use a Tandem propeller_array arrangement".to_string());
+        }
+        let mut base = base_config.clone();
+        base.batch_mode = None;
+        base.pitch_control = PitchControl::Sweep;
+        base.pitch_optimizer.mode = OptimizerMode::LinearSweep;
+        base.propeller_array.count = 2;
+        base.propeller_array.counter_rotating = true;
+        base.output_dir = base_config.output_dir.join("torque_balance");
+
+        let pitches: Vec<f32> = (0..).map_while(|index| base.pitch_at(index)).collect();
+        let mut balances = Vec::new();
+        for pitch in pitches {
+            // Tandem stacks the first rotor lowest, so it runs at ratio times the upper
+            // rotor's speed and the upper one at 1 / ratio of that
+            let net_torque = |ratio: f32| -> Result<f32, String> {
+                let mut config = base.clone();
+                config.pitch_values = Some(vec![pitch]);
+                config.rotor_governor = Some(RotorGovernor::ConstantRPM { target_rpm: upper_rpm * ratio, kp, ki, kd });
+                config.propeller_array.alternate_rpm_ratio = Some(1.0 / ratio);
+                let results = PropellerTestRig::run(config)?;
+                results.first().map(|result| result.net_torque).ok_or_else(|| format!("the run at pitch {} finished no pitch", pitch))
+            };
+            let balance = bisect(RATIO_BRACKET.0, RATIO_BRACKET.1, tolerance, MAX_ITERATIONS, net_torque)?;
+            balances.push(TorqueBalance { pitch, balance });
+        }
+        Ok(balances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bisection_finds_the_root_within_tolerance() {
+        let root = bisect(0.5, 2.0, 1e-3, 30, |x| Ok(x * x - 1.44)).unwrap().unwrap();
+        assert!(root.converged);
+        assert!((root.x - 1.2).abs() < 1e-3);
+        assert!(root.value.abs() < 1e-3);
+    }
+
+    #[test]
+    fn bisection_without_a_sign_change_has_no_root() {
+        assert_eq!(bisect(0.5, 2.0, 1e-3, 30, |x| Ok(x + 1.0)), Ok(None));
+    }
+
+    #[test]
+    fn bisection_stops_after_max_iterations() {
+        let mut evaluations = 0;
+        let root = bisect(0.0, 1.0, 1e-9, 3, |x| {
+            evaluations += 1;
+            Ok(x - 0.3)
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(evaluations, 2 + 3);
+        assert!(!root.converged);
+        assert_eq!(root.x, 0.375);
+    }
+
+    #[test]
+    fn bisection_passes_run_errors_on() {
+        assert_eq!(bisect(0.5, 2.0, 1e-3, 30, |_| Err("rig failed".to_string())), Err("rig failed".to_string()));
+    }
+}